@@ -1,70 +1,454 @@
-use crate::OkOrStringError;
-
-pub fn help() -> OkOrStringError {
-    println!("RTI converts Unix epoch time to a human readable format and vice versa.");
-    println!("Enter values to convert separated by a space.\n");
-    println!("Additional commands:");
-    println!("    help -  View this message.");
-    println!("    set-tz - Set a configured timezone. Uses first argument after set-tz.");
-    println!("    clear-tz - Clear timezone config.");
-    println!("    add-token - Add a custom parsing token. Uses first argument after add-token. See https://docs.rs/chrono/0.4.0/chrono/format/strftime/index.html for syntax.");
-    println!("    remove-token - Remove a custom parsing token. No changes made if the token doesn't exist.");
-    println!("    view-tokens - See a list of stored custom parsing tokens.");
-    Ok(None)
+use crate::completion::{stored_tokens, stored_zones, timezones, SHELLS};
+use crate::datetime_parsing::EpochUnit;
+use crate::json_fields::FieldPath;
+use crate::radix::{Endian, Radix};
+use crate::relative::MAX_GRANULARITY;
+use crate::settings::OutputMode;
+use crate::template::PLACEHOLDERS;
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::ArgValueCandidates;
+
+// Options shared by conversions and every subcommand. Each one can go anywhere on the line.
+#[derive(Args, Default)]
+pub struct Options {
+    /// Timezone for this run, overriding the TIMEZONE env variable and any configured timezone. An IANA name, a fixed offset such as +05:30 or UTC-3, or a POSIX TZ rule such as EST5EDT,M3.2.0,M11.1.0.
+    #[arg(long, global = true, value_name = "TZ", add = ArgValueCandidates::new(timezones))]
+    pub tz: Option<String>,
+
+    /// Output format for this run, overriding any configured format. A preset (iso, rfc3339, rfc2822, http, sql, unix-date) or a strftime string.
+    #[arg(long, global = true, value_name = "PRESET|STRFTIME")]
+    pub format: Option<String>,
+
+    /// How epoch input is read and epoch output is written. auto reads 13+ digits as milliseconds.
+    #[arg(
+        long,
+        global = true,
+        default_value = "auto",
+        hide_default_value = true,
+        value_parser = PossibleValuesParser::new(["auto", "s", "ms", "us", "ns"])
+            .map(|val| EpochUnit::from_string(&val).unwrap_or_default()),
+    )]
+    pub unit: EpochUnit,
+
+    /// Show each conversion in these timezones for this run, overriding any configured zones.
+    #[arg(
+        long,
+        global = true,
+        value_name = "TZ,TZ,...",
+        value_delimiter = ',',
+        value_parser = parse_list_item
+    )]
+    pub zones: Option<Vec<String>>,
+
+    /// How conversions are printed.
+    #[arg(
+        long,
+        global = true,
+        default_value = "text",
+        hide_default_value = true,
+        value_parser = PossibleValuesParser::new(["text", "json", "ndjson", "csv", "tsv", "markdown"])
+            .map(|val| OutputMode::from_string(&val).unwrap_or_default()),
+    )]
+    pub output: OutputMode,
+
+    /// Same as --output json: a JSON array of objects.
+    #[arg(long, global = true, conflicts_with_all = ["output", "ndjson"])]
+    pub json: bool,
+
+    /// Same as --output ndjson: one JSON object per line.
+    #[arg(long, global = true, conflicts_with = "output")]
+    pub ndjson: bool,
+
+    /// Columns for csv, tsv and markdown output: input, kind, epoch, ms, us, ns, iso, local, relative, zones, error or any timezone.
+    #[arg(
+        long,
+        global = true,
+        value_name = "COL,COL,...",
+        value_delimiter = ',',
+        value_parser = parse_list_item
+    )]
+    pub columns: Option<Vec<String>>,
+
+    /// Print each conversion with this template instead of "input => output" and the Timezone footer.
+    #[arg(long, global = true, long_help = format!("Print each conversion with this template instead of \"input => output\" and the Timezone footer.\nPlaceholders: {{{}}}. Use {{{{ and }}}} for literal braces.", PLACEHOLDERS.replace(", ", "}, {")))]
+    pub template: Option<String>,
+
+    /// Locale for month and weekday names, e.g. fr_FR, de_DE or ja_JP. Without a format, uses the locale's own date and time format.
+    #[arg(long, global = true)]
+    pub locale: Option<String>,
+
+    /// Also show how far each value is from now, e.g. "3 days ago" or "in 2d 3h 4m 5s".
+    #[arg(long, global = true, value_parser = ["human", "exact"])]
+    pub relative: Option<String>,
+
+    /// Number of units in humanized relative output. Defaults to 1.
+    #[arg(long, global = true, value_name = "N", value_parser = parse_granularity)]
+    pub granularity: Option<usize>,
+
+    /// Measure relative output from this date/time or epoch instead of now.
+    #[arg(long, global = true, value_name = "DATE/TIME OR EPOCH")]
+    pub relative_to: Option<String>,
+
+    /// Also show each conversion in seconds, milliseconds, microseconds and nanoseconds.
+    #[arg(long, global = true)]
+    pub units: bool,
+
+    /// Also show the weekday, ISO week, day of year, quarter, offset, DST and next transition for each conversion.
+    #[arg(long, global = true)]
+    pub verbose: bool,
+
+    /// Convert every value even when some can't be, then exit with status 1. This is the default.
+    #[arg(long, global = true, overrides_with = "fail_fast")]
+    pub keep_going: bool,
+
+    /// Stop at the first value that can't be converted and exit with status 1.
+    #[arg(long, global = true, overrides_with = "keep_going")]
+    pub fail_fast: bool,
+
+    /// Radix used when printing epochs. Input epochs may always use 0x, 0o or 0b prefixes.
+    #[arg(
+        long,
+        global = true,
+        default_value = "dec",
+        hide_default_value = true,
+        value_parser = PossibleValuesParser::new(["dec", "hex", "oct", "bin"])
+            .map(|val| Radix::from_string(&val).unwrap_or_default()),
+    )]
+    pub radix: Radix,
+
+    /// Byte order for raw byte input such as "99 a3 62 62". Defaults to big.
+    #[arg(
+        long,
+        global = true,
+        default_value = "big",
+        hide_default_value = true,
+        value_parser = PossibleValuesParser::new(["big", "little"])
+            .map(|val| Endian::from_string(&val).unwrap_or_default()),
+    )]
+    pub endian: Endian,
 }
 
-pub struct ParsedInput {
-    pub action: Option<Action>,
-    pub second_arg: Option<String>,
-    pub date_args: Vec<String>,
+impl Options {
+    pub fn output_mode(&self) -> OutputMode {
+        match (self.json, self.ndjson) {
+            (true, _) => OutputMode::Json,
+            (_, true) => OutputMode::Ndjson,
+            _ => self.output,
+        }
+    }
 }
 
-pub fn parse_input(input: Vec<String>) -> Result<ParsedInput, String> {
-    let input_len = input.len();
-    if input_len == 1 {
-        return Err("Must include at least one argument!".to_string());
+fn parse_granularity(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(val) if val > 0 => Ok(val.min(MAX_GRANULARITY)),
+        _ => Err(format!("Invalid granularity: {}", value)),
     }
-    let maybe_action = match input[1].parse() {
-        Ok(val) => Action::from_string(val),
-        Err(_) => return Err("Error parsing arg.".to_string()),
-    };
-
-    let second_arg: Option<String> = input.get(2).cloned();
-
-    // if we have an action, we aren't doing any datetime parsing
-    // so we just give empty list, otherwise all of our args
-    // are datetimes to parse
-    let date_args = match maybe_action {
-        Some(_) => vec![],
-        None => input[1..].iter().map(|x| x.to_owned()).collect(),
-    };
-
-    Ok(ParsedInput {
-        action: maybe_action,
-        second_arg,
-        date_args,
-    })
 }
 
+fn parse_list_item(value: &str) -> Result<String, String> {
+    // so "UTC, Asia/Tokyo" works as well as "UTC,Asia/Tokyo"
+    Ok(value.trim().to_string())
+}
+
+/// RTI converts Unix epoch time to a human readable format and vice versa.
+#[derive(Parser)]
+#[command(
+    name = "rti",
+    version,
+    args_conflicts_with_subcommands = true,
+    after_help = "Use -- before values that look like commands or options, e.g. rti -- help.",
+    after_long_help = crate::manual::long_help()
+)]
+pub struct Cli {
+    /// Epochs, date/times, ObjectIds or keywords (now, yesterday, tomorrow) to convert. Use - or pipe values in to convert each line of stdin.
+    #[arg(value_name = "VALUES", allow_negative_numbers = true)]
+    pub values: Vec<String>,
+
+    #[command(flatten)]
+    pub options: Options,
+
+    #[command(subcommand)]
+    pub action: Option<Action>,
+}
+
+#[derive(Subcommand)]
 pub enum Action {
-    Help,
-    SetTz,
+    /// Set a configured timezone.
+    SetTz {
+        /// Timezone name, e.g. America/New_York, a fixed offset such as +05:30, or a POSIX TZ rule.
+        #[arg(add = ArgValueCandidates::new(timezones))]
+        timezone: String,
+    },
+    /// Clear timezone config.
     ClearTz,
-    AddToken,
-    RemoveToken,
+    /// Add a custom parsing token. See https://docs.rs/chrono/0.4.0/chrono/format/strftime/index.html for syntax.
+    AddToken {
+        /// strftime pattern for a full date and time, e.g. "%d-%m-%y %H:%M".
+        token: String,
+    },
+    /// Remove a custom parsing token. No changes made if the token doesn't exist.
+    RemoveToken {
+        /// A token previously added with add-token.
+        #[arg(add = ArgValueCandidates::new(stored_tokens))]
+        token: String,
+    },
+    /// See a list of stored custom parsing tokens.
     ViewTokens,
+    /// Set a configured output format for epoch to date/time conversion. Accepts a preset (iso, rfc3339, rfc2822, http, sql, unix-date) or a strftime string.
+    SetFormat {
+        /// Preset name, optionally with a precision such as iso:ms, or a strftime string.
+        format: String,
+    },
+    /// Clear output format config.
+    ClearFormat,
+    /// Set a configured output template. See --template for placeholders.
+    SetTemplate {
+        /// Template such as "{input} => {iso}".
+        template: String,
+    },
+    /// Clear output template config.
+    ClearTemplate,
+    /// Set a configured locale for month and weekday names, e.g. fr_FR.
+    SetLocale {
+        /// Locale name, e.g. fr_FR, de_DE or ja_JP.
+        locale: String,
+    },
+    /// Clear locale config.
+    ClearLocale,
+    /// Show conversions in an extra timezone, one column per zone.
+    AddZone {
+        /// Timezone name, e.g. Asia/Tokyo.
+        #[arg(add = ArgValueCandidates::new(timezones))]
+        timezone: String,
+    },
+    /// Stop showing a timezone column.
+    RemoveZone {
+        /// A zone previously added with add-zone.
+        #[arg(add = ArgValueCandidates::new(stored_zones))]
+        timezone: String,
+    },
+    /// See a list of configured display zones.
+    ViewZones,
+    /// Decode Snowflake IDs with the named layout.
+    Snowflake {
+        /// Layout name, see view-snowflakes.
+        layout: String,
+        /// Snowflake IDs to decode.
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Build the lowest Snowflake ID with the named layout for each date/time or epoch.
+    SnowflakeAt {
+        /// Layout name, see view-snowflakes.
+        layout: String,
+        /// Date/times or epochs.
+        #[arg(required = true, allow_negative_numbers = true)]
+        values: Vec<String>,
+    },
+    /// Add a custom Snowflake layout.
+    AddSnowflake {
+        /// Name to refer to the layout by.
+        name: String,
+        /// Custom epoch in milliseconds.
+        epoch_ms: String,
+        /// Number of bits holding the worker ID.
+        worker_bits: String,
+        /// Number of bits holding the sequence number.
+        sequence_bits: String,
+    },
+    /// Remove a custom Snowflake layout by name.
+    RemoveSnowflake {
+        /// A layout previously added with add-snowflake.
+        name: String,
+    },
+    /// See a list of built-in and custom Snowflake layouts.
+    ViewSnowflakes,
+    /// Build the lowest MongoDB ObjectId for each date/time or epoch, for use in range queries.
+    #[command(name = "objectid-at")]
+    ObjectIdAt {
+        /// Date/times or epochs.
+        #[arg(required = true, allow_negative_numbers = true)]
+        values: Vec<String>,
+    },
+    /// Show every plausible unit and epoch base for each number, most plausible first.
+    Inspect {
+        /// Numbers to interpret.
+        #[arg(required = true, allow_negative_numbers = true)]
+        values: Vec<String>,
+    },
+    /// Rewrite epochs found in each line of stdin as date/times, leaving the rest of the line as is.
+    ///
+    /// For example tail -f app.log | rti filter. Numbers of 10, 13, 16 or 19 digits are read as seconds, ms, us or ns; with --unit only that unit's length is converted.
+    Filter {
+        /// Keep each timestamp and add the conversion after it in brackets instead of replacing it.
+        #[arg(long)]
+        append: bool,
+        /// Also convert date/times the usual parsing recognises (including custom tokens) to epochs.
+        #[arg(long)]
+        dates: bool,
+    },
+    /// Convert timestamp columns of a CSV file, in either direction, keeping everything else as is.
+    Csv {
+        /// Column to convert, by header name or 1-based index. Repeat for more columns.
+        #[arg(long, value_name = "NAME|INDEX", required = true)]
+        column: Vec<String>,
+        /// Add each conversion as a new column after the original (named <column>_converted) instead of replacing it.
+        #[arg(long)]
+        add: bool,
+        /// The first row is data, not a header. Columns must then be given by index.
+        #[arg(long)]
+        no_header: bool,
+        /// CSV file to read. Reads stdin when omitted or -.
+        file: Option<std::path::PathBuf>,
+    },
+    /// Convert timestamp fields inside JSON or NDJSON documents, in either direction.
+    ///
+    /// Numbers become date/times and date/time strings become epochs. Missing fields are skipped, and lines that aren't JSON pass through untouched.
+    Json {
+        /// Field to convert, e.g. .ts, .meta.created or .events[].ts. Repeat for more fields.
+        #[arg(long, value_name = "PATH", required = true, value_parser = FieldPath::parse)]
+        field: Vec<FieldPath>,
+        /// Add each conversion as a sibling field (named <field>_converted) instead of replacing it.
+        #[arg(long)]
+        add: bool,
+        /// JSON or NDJSON file to read. Reads stdin when omitted or -.
+        file: Option<std::path::PathBuf>,
+    },
+    /// Print a completion script for your shell.
+    ///
+    /// For example rti completions bash > ~/.local/share/bash-completion/completions/rti. Timezones and stored tokens are completed too.
+    Completions {
+        /// Shell to complete in.
+        #[arg(value_parser = SHELLS)]
+        shell: String,
+    },
+    /// Convert values interactively, with line editing and history.
+    ///
+    /// Settings are read once, then :tz, :fmt and :unit change them for the session. _ is the previous result, e.g. _ + 1d. See :help.
+    Repl,
+    /// Print the man page, e.g. rti man > ~/.local/share/man/man1/rti.1
+    Man,
+    /// Convert values like normal and show a full breakdown of each one. Same as --verbose.
+    Explain {
+        /// Values to convert.
+        #[arg(required = true, allow_negative_numbers = true)]
+        values: Vec<String>,
+    },
 }
 
-impl Action {
-    fn from_string(input: String) -> Option<Action> {
-        match input.as_str() {
-            "help" => Some(Action::Help),
-            "set-tz" => Some(Action::SetTz),
-            "clear-tz" => Some(Action::ClearTz),
-            "add-token" => Some(Action::AddToken),
-            "remove-token" => Some(Action::RemoveToken),
-            "view-tokens" => Some(Action::ViewTokens),
-            _ => None,
+#[cfg(test)]
+mod cli_tests {
+    use super::{Action, Cli};
+    use crate::datetime_parsing::EpochUnit;
+    use crate::settings::OutputMode;
+    use clap::Parser;
+
+    fn try_parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("rti").chain(args.iter().copied()))
+    }
+
+    fn parse(args: &[&str]) -> Cli {
+        try_parse(args).unwrap()
+    }
+
+    #[test]
+    fn test_values() {
+        let cli = parse(&["1650627609", "-86400", "2022-04-22 11:40:09"]);
+        assert!(cli.action.is_none());
+        assert_eq!(
+            cli.values,
+            vec!["1650627609", "-86400", "2022-04-22 11:40:09"]
+        );
+    }
+
+    #[test]
+    fn test_double_dash() {
+        let cli = parse(&["--", "help", "--json"]);
+        assert!(cli.action.is_none());
+        assert_eq!(cli.values, vec!["help", "--json"]);
+    }
+
+    #[test]
+    fn test_global_options() {
+        let cli = parse(&["--unit", "ms", "1650627609123", "--tz=Asia/Tokyo", "--json"]);
+        assert_eq!(cli.options.unit, EpochUnit::Millis);
+        assert_eq!(cli.options.tz.as_deref(), Some("Asia/Tokyo"));
+        assert_eq!(cli.options.output_mode(), OutputMode::Json);
+
+        let cli = parse(&["inspect", "1650627609", "--format", "iso"]);
+        assert!(matches!(cli.action, Some(Action::Inspect { .. })));
+        assert_eq!(cli.options.format.as_deref(), Some("iso"));
+    }
+
+    #[test]
+    fn test_lists() {
+        let cli = parse(&["--zones", "Asia/Tokyo,Europe/London", "1"]);
+        assert_eq!(
+            cli.options.zones,
+            Some(vec!["Asia/Tokyo".to_string(), "Europe/London".to_string()])
+        );
+        let cli = parse(&[
+            "--zones",
+            "UTC, Asia/Tokyo",
+            "--columns",
+            "input, epoch",
+            "1",
+        ]);
+        assert_eq!(
+            cli.options.zones,
+            Some(vec!["UTC".to_string(), "Asia/Tokyo".to_string()])
+        );
+        assert_eq!(
+            cli.options.columns,
+            Some(vec!["input".to_string(), "epoch".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_actions() {
+        match parse(&["set-tz", "America/New_York"]).action {
+            Some(Action::SetTz { timezone }) => assert_eq!(timezone, "America/New_York"),
+            _ => panic!("expected set-tz"),
+        }
+        match parse(&["snowflake", "twitter", "1541815603606036480"]).action {
+            Some(Action::Snowflake { layout, ids }) => {
+                assert_eq!(layout, "twitter");
+                assert_eq!(ids, vec!["1541815603606036480"]);
+            }
+            _ => panic!("expected snowflake"),
+        }
+        // --column belongs to csv and must not be taken by the global --columns
+        let cli = parse(&[
+            "csv",
+            "--column",
+            "created_at",
+            "--column",
+            "3",
+            "events.csv",
+        ]);
+        assert!(cli.options.columns.is_none());
+        match cli.action {
+            Some(Action::Csv { column, file, .. }) => {
+                assert_eq!(column, vec!["created_at", "3"]);
+                assert_eq!(file, Some("events.csv".into()));
+            }
+            _ => panic!("expected csv"),
         }
     }
+
+    #[test]
+    fn test_invalid() {
+        assert!(try_parse(&["--radix", "base64", "1"]).is_err());
+        assert!(try_parse(&["--granularity", "0", "1"]).is_err());
+        assert_eq!(
+            parse(&["--granularity", "18446744073709551615", "1"])
+                .options
+                .granularity,
+            Some(6)
+        );
+        assert!(try_parse(&["--bogus", "1"]).is_err());
+        assert!(try_parse(&["set-tz"]).is_err());
+        assert!(try_parse(&["--json", "--output", "csv", "1"]).is_err());
+    }
 }
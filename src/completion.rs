@@ -0,0 +1,81 @@
+// Shell completion. The scripts call back into rti (COMPLETE=<shell> rti -- <words>),
+// so values that live in the config, like custom tokens, are always current.
+
+use crate::config::{get_custom_tokens, get_display_zones};
+use clap::CommandFactory;
+use clap_complete::env::Shells;
+use clap_complete::CompletionCandidate;
+use std::io::{self, Write};
+
+pub const COMPLETE_VAR: &str = "COMPLETE";
+pub const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+pub fn timezones() -> Vec<CompletionCandidate> {
+    chrono_tz::TZ_VARIANTS
+        .iter()
+        .map(|zone| CompletionCandidate::new(zone.name()))
+        .collect()
+}
+
+pub fn stored_tokens() -> Vec<CompletionCandidate> {
+    // a config that can't be read just means nothing to offer
+    get_custom_tokens()
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+pub fn stored_zones() -> Vec<CompletionCandidate> {
+    get_display_zones()
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+pub fn complete_from_env() {
+    // exits once it has answered when the shell is asking for completions, otherwise does nothing
+    clap_complete::CompleteEnv::with_factory(crate::cli::Cli::command)
+        .var(COMPLETE_VAR)
+        .complete();
+}
+
+pub fn write_script<W: Write>(shell: &str, out: &mut W) -> io::Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells.completer(shell).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Unsupported shell: {}", shell),
+        )
+    })?;
+    let name = crate::cli::Cli::command().get_name().to_string();
+    completer.write_registration(COMPLETE_VAR, &name, &name, &name, out)
+}
+
+#[cfg(test)]
+mod completion_tests {
+    use super::{timezones, write_script, SHELLS};
+
+    #[test]
+    fn test_timezones() {
+        let names: Vec<String> = timezones()
+            .iter()
+            .map(|val| val.get_value().to_string_lossy().to_string())
+            .collect();
+        assert!(names.contains(&"America/New_York".to_string()));
+        assert!(names.contains(&"Asia/Tokyo".to_string()));
+    }
+
+    #[test]
+    fn test_scripts() {
+        for shell in SHELLS {
+            let mut out: Vec<u8> = Vec::new();
+            write_script(shell, &mut out).unwrap();
+            let script = String::from_utf8(out).unwrap();
+            assert!(script.contains("COMPLETE"), "{}", shell);
+            assert!(script.contains("rti"), "{}", shell);
+        }
+        assert!(write_script("tcsh", &mut Vec::new()).is_err());
+    }
+}
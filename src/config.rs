@@ -1,5 +1,8 @@
-use crate::OkOrStringError;
-use chrono_tz::{ParseError, Tz, UTC};
+use crate::failure::{Failure, OkOrFailure};
+use crate::output_format::{parse_locale, OutputFormat};
+use crate::snowflake::{presets, SnowflakeLayout};
+use crate::template::Template;
+use crate::timezone::Zone;
 use serde::{Deserialize, Serialize};
 use std::{env, fmt};
 
@@ -7,6 +10,11 @@ use std::{env, fmt};
 pub struct MyConfig {
     pub default_timezone: Option<String>,
     pub custom_parsing_tokens: Option<Vec<String>>,
+    pub snowflake_layouts: Option<Vec<SnowflakeLayout>>,
+    pub output_format: Option<String>,
+    pub display_zones: Option<Vec<String>>,
+    pub template: Option<String>,
+    pub locale: Option<String>,
 }
 
 impl fmt::Display for MyConfig {
@@ -15,33 +23,37 @@ impl fmt::Display for MyConfig {
     }
 }
 
-pub fn load_config() -> MyConfig {
-    confy::load("rti").expect("Unable to load config.")
+pub fn load_config() -> Result<MyConfig, Failure> {
+    confy::load("rti").map_err(|e| Failure::Config(format!("Unable to load config: {}", e)))
 }
 
-pub fn set_tz_config(tz_input: Option<String>) -> OkOrStringError {
-    // Verify that this can be parsed as a timezone. Notify and exit if it can't.
-    let timezone: Tz = match tz_input {
-        Some(val) => match val.parse() {
+pub fn set_tz_config(tz_input: Option<String>) -> OkOrFailure {
+    // Verify that this can be parsed as a timezone, offset or POSIX rule. Notify and exit if it can't.
+    let timezone: Zone = match tz_input {
+        Some(val) => match Zone::parse(&val) {
             Ok(v) => v,
-            Err(_) => return Err("Invalid timezone provided.".to_string()),
+            Err(_) => return Err(Failure::Usage("Invalid timezone provided.".to_string())),
         },
-        None => return Err("Must provide timezone argument.".to_string()),
+        None => {
+            return Err(Failure::Usage(
+                "Must provide timezone argument.".to_string(),
+            ))
+        }
     };
-    let existing_config = load_config();
+    let existing_config = load_config()?;
     let new_config = MyConfig {
         default_timezone: Some(timezone.to_string()),
         ..existing_config
     };
     match confy::store("rti", new_config) {
         Ok(_) => Ok(Some(format!("Timezone updated to {}", timezone))),
-        Err(e) => Err(format!("Error storing timezone: {}", e)),
+        Err(e) => Err(Failure::Config(format!("Error storing timezone: {}", e))),
     }
 }
 
-pub fn clear_tz_config() -> OkOrStringError {
+pub fn clear_tz_config() -> OkOrFailure {
     // change default timezone to None and save.
-    let existing_config = load_config();
+    let existing_config = load_config()?;
     let new_config = MyConfig {
         default_timezone: None,
         ..existing_config
@@ -49,83 +61,269 @@ pub fn clear_tz_config() -> OkOrStringError {
 
     match confy::store("rti", new_config) {
         Ok(_) => Ok(Some("Timezone cleared.".to_string())),
-        Err(e) => Err(format!("Error storing timezone: {}", e)),
+        Err(e) => Err(Failure::Config(format!("Error storing timezone: {}", e))),
     }
 }
 
-fn get_env_timezone() -> Option<Tz> {
-    // Check for TIMEZONE env variable
-    let env_timezone = env::var("TIMEZONE");
-    if let Ok(env_tz) = env_timezone {
-        // If TIMEZONE env var is found, try to parse into timezone
-        // and panic if it cant be parsed
-        let parsed_env_tz: Result<Tz, ParseError> = env_tz.parse();
-        match parsed_env_tz {
-            Ok(tz) => return Some(tz),
-            _ => panic!("Unable to parse TIMEZONE env variable."),
-        }
-    };
-    None
+fn get_env_timezone() -> Result<Option<Zone>, Failure> {
+    // Check for TIMEZONE env variable, which has to parse if it's set
+    match env::var("TIMEZONE") {
+        Ok(val) => match Zone::parse(&val) {
+            Ok(tz) => Ok(Some(tz)),
+            Err(_) => Err(Failure::Config(format!(
+                "Unable to parse TIMEZONE env variable: {}",
+                val
+            ))),
+        },
+        Err(_) => Ok(None),
+    }
 }
 
-pub fn get_timezone() -> Tz {
+pub fn get_timezone() -> Result<Zone, Failure> {
     // Get Timezone, returning the first found in the following order:
     // 1. env
     // 2. config
     // 3. default to UTC
 
-    let env_timezone = get_env_timezone();
-    if let Some(tz) = env_timezone {
-        return tz;
+    if let Some(tz) = get_env_timezone()? {
+        return Ok(tz);
     }
 
-    let existing_config = load_config();
-    if let Some(val) = existing_config.default_timezone {
-        let tz: Tz = val.parse().unwrap();
-        tz
-    } else {
-        UTC
+    match load_config()?.default_timezone {
+        Some(val) => Zone::parse(&val)
+            .map_err(|_| Failure::Config(format!("Invalid timezone in config: {}", val))),
+        None => Ok(Zone::default()),
     }
 }
 
-pub fn get_custom_tokens() -> Vec<String> {
-    // Return vec of custom tokens and return an empty vec if none exist
-    let existing_config = load_config();
-    match existing_config.custom_parsing_tokens {
-        Some(tokens) => tokens,
-        None => Vec::new(),
+pub fn get_output_format() -> Result<Option<String>, Failure> {
+    Ok(load_config()?.output_format)
+}
+
+pub fn set_format_config(format_input: Option<String>) -> OkOrFailure {
+    let format: String = match format_input {
+        Some(val) => val,
+        None => return Err(Failure::Usage("Must provide format argument.".to_string())),
+    };
+    OutputFormat::parse(&format)?;
+    let new_config = MyConfig {
+        output_format: Some(format.clone()),
+        ..load_config()?
+    };
+    match confy::store("rti", new_config) {
+        Ok(_) => Ok(Some(format!("Output format updated to {}", format))),
+        Err(e) => Err(Failure::Config(format!(
+            "Error storing output format: {}",
+            e
+        ))),
     }
 }
 
-pub fn add_custom_token(new_token: Option<String>) -> OkOrStringError {
-    let token: String = match new_token {
+pub fn clear_format_config() -> OkOrFailure {
+    let new_config = MyConfig {
+        output_format: None,
+        ..load_config()?
+    };
+    match confy::store("rti", new_config) {
+        Ok(_) => Ok(Some("Output format cleared.".to_string())),
+        Err(e) => Err(Failure::Config(format!(
+            "Error storing output format: {}",
+            e
+        ))),
+    }
+}
+
+pub fn get_locale() -> Result<Option<String>, Failure> {
+    Ok(load_config()?.locale)
+}
+
+pub fn set_locale_config(locale_input: Option<String>) -> OkOrFailure {
+    let locale = match locale_input {
+        Some(val) => parse_locale(&val)?,
+        None => return Err(Failure::Usage("Must provide locale argument.".to_string())),
+    };
+    let new_config = MyConfig {
+        locale: Some(locale.to_string()),
+        ..load_config()?
+    };
+    match confy::store("rti", new_config) {
+        Ok(_) => Ok(Some(format!("Locale updated to {}", locale))),
+        Err(e) => Err(Failure::Config(format!("Error storing locale: {}", e))),
+    }
+}
+
+pub fn clear_locale_config() -> OkOrFailure {
+    let new_config = MyConfig {
+        locale: None,
+        ..load_config()?
+    };
+    match confy::store("rti", new_config) {
+        Ok(_) => Ok(Some("Locale cleared.".to_string())),
+        Err(e) => Err(Failure::Config(format!("Error storing locale: {}", e))),
+    }
+}
+
+pub fn get_template() -> Result<Option<String>, Failure> {
+    Ok(load_config()?.template)
+}
+
+pub fn set_template_config(template_input: Option<String>) -> OkOrFailure {
+    let template: String = match template_input {
         Some(val) => val,
-        None => return Err("Must provide timezone argument.".to_string()),
+        None => {
+            return Err(Failure::Usage(
+                "Must provide template argument.".to_string(),
+            ))
+        }
     };
+    Template::parse(&template)?;
+    let new_config = MyConfig {
+        template: Some(template.clone()),
+        ..load_config()?
+    };
+    match confy::store("rti", new_config) {
+        Ok(_) => Ok(Some(format!("Output template updated to {}", template))),
+        Err(e) => Err(Failure::Config(format!(
+            "Error storing output template: {}",
+            e
+        ))),
+    }
+}
 
-    let mut existing_tokens = match load_config().custom_parsing_tokens {
-        Some(tokens) => tokens,
-        None => Vec::new(),
+pub fn clear_template_config() -> OkOrFailure {
+    let new_config = MyConfig {
+        template: None,
+        ..load_config()?
+    };
+    match confy::store("rti", new_config) {
+        Ok(_) => Ok(Some("Output template cleared.".to_string())),
+        Err(e) => Err(Failure::Config(format!(
+            "Error storing output template: {}",
+            e
+        ))),
+    }
+}
+
+pub fn get_display_zones() -> Result<Vec<String>, Failure> {
+    Ok(load_config()?.display_zones.unwrap_or_default())
+}
+
+pub fn add_display_zone(tz_input: Option<String>) -> OkOrFailure {
+    let timezone: Zone = match tz_input {
+        Some(val) => match Zone::parse(&val) {
+            Ok(v) => v,
+            Err(_) => return Err(Failure::Usage("Invalid timezone provided.".to_string())),
+        },
+        None => {
+            return Err(Failure::Usage(
+                "Must provide timezone argument.".to_string(),
+            ))
+        }
+    };
+    let mut zones = get_display_zones()?;
+    if zones.contains(&timezone.to_string()) {
+        return Ok(Some(format!("{} is already displayed.", timezone)));
+    }
+    zones.push(timezone.to_string());
+    let new_config = MyConfig {
+        display_zones: Some(zones),
+        ..load_config()?
+    };
+    match confy::store("rti", new_config) {
+        Ok(_) => Ok(Some(format!("Added display zone {}", timezone))),
+        Err(e) => Err(Failure::Config(format!(
+            "Error storing display zone: {}",
+            e
+        ))),
+    }
+}
+
+pub fn remove_display_zone(to_remove: Option<String>) -> OkOrFailure {
+    // stored zones are in their parsed form, so +0530 removes +05:30
+    let zone: String = match to_remove {
+        Some(val) => Zone::parse(&val)
+            .map(|zone| zone.to_string())
+            .unwrap_or(val),
+        None => {
+            return Err(Failure::Usage(
+                "Must provide timezone to remove.".to_string(),
+            ))
+        }
+    };
+    let existing_zones = get_display_zones()?;
+    if !existing_zones.contains(&zone) {
+        return Ok(Some("No matching display zone found.".to_string()));
+    }
+    let filtered_zones: Vec<String> = existing_zones
+        .into_iter()
+        .filter(|val| val != &zone)
+        .collect();
+
+    let zones_to_store = match filtered_zones.len() {
+        0 => None,
+        _ => Some(filtered_zones),
     };
+    let new_config = MyConfig {
+        display_zones: zones_to_store,
+        ..load_config()?
+    };
+    match confy::store("rti", new_config) {
+        Ok(_) => Ok(Some("Display zone removed.".to_string())),
+        Err(e) => Err(Failure::Config(format!(
+            "Error removing display zone: {}",
+            e
+        ))),
+    }
+}
+
+pub fn view_display_zones() -> OkOrFailure {
+    let mut lines: Vec<String> = vec!["Display zones:".to_string()];
+    match load_config()?.display_zones {
+        Some(mut zones) => lines.append(&mut zones),
+        // nothing stored yet isn't an error, just an empty list
+        None => return Ok(Some("No display zones configured.".to_string())),
+    };
+    Ok(Some(lines.join("\n")))
+}
+
+pub fn get_custom_tokens() -> Result<Vec<String>, Failure> {
+    // Return vec of custom tokens and return an empty vec if none exist
+    Ok(load_config()?.custom_parsing_tokens.unwrap_or_default())
+}
+
+pub fn add_custom_token(new_token: Option<String>) -> OkOrFailure {
+    let token: String = match new_token {
+        Some(val) => val,
+        None => {
+            return Err(Failure::Usage(
+                "Must provide timezone argument.".to_string(),
+            ))
+        }
+    };
+
+    let mut existing_tokens = load_config()?.custom_parsing_tokens.unwrap_or_default();
 
     existing_tokens.push(token);
-    let existing_config = load_config();
+    let existing_config = load_config()?;
     let new_config = MyConfig {
         custom_parsing_tokens: Some(existing_tokens),
         ..existing_config
     };
     match confy::store("rti", new_config) {
         Ok(_) => Ok(Some("Custom Token successfully added.".to_string())),
-        Err(e) => Err(format!("Error storing custom token: {}", e)),
+        Err(e) => Err(Failure::Config(format!(
+            "Error storing custom token: {}",
+            e
+        ))),
     }
 }
 
-pub fn remove_custom_token(to_remove: Option<String>) -> OkOrStringError {
+pub fn remove_custom_token(to_remove: Option<String>) -> OkOrFailure {
     let token: String = match to_remove {
         Some(val) => val,
-        None => return Err("Must provide token to remove.".to_string()),
+        None => return Err(Failure::Usage("Must provide token to remove.".to_string())),
     };
-    let existing_tokens: Vec<String> = match load_config().custom_parsing_tokens {
+    let existing_tokens: Vec<String> = match load_config()?.custom_parsing_tokens {
         Some(tokens) => tokens,
         None => {
             return Ok(Some("No tokens to remove.".to_string()));
@@ -146,21 +344,145 @@ pub fn remove_custom_token(to_remove: Option<String>) -> OkOrStringError {
 
     let new_config = MyConfig {
         custom_parsing_tokens: tokens_to_store,
-        ..load_config()
+        ..load_config()?
     };
 
     match confy::store("rti", new_config) {
         Ok(_) => Ok(Some("Custom token removed.".to_string())),
-        Err(e) => Err(format!("Error removing custom token: {}", e)),
+        Err(e) => Err(Failure::Config(format!(
+            "Error removing custom token: {}",
+            e
+        ))),
     }
 }
 
-pub fn view_tokens() -> OkOrStringError {
+pub fn view_tokens() -> OkOrFailure {
     // get tokens from config and print them out
     let mut existing_tokens: Vec<String> = vec!["Custom datetime tokens:".to_string()];
-    match load_config().custom_parsing_tokens {
+    match load_config()?.custom_parsing_tokens {
         Some(mut tokens) => existing_tokens.append(&mut tokens),
-        None => return Err("No custom tokens exist!".to_string()),
+        None => return Ok(Some("No custom tokens exist.".to_string())),
     };
     Ok(Some(existing_tokens.join("\n")))
 }
+
+pub fn get_snowflake_layouts() -> Result<Vec<SnowflakeLayout>, Failure> {
+    // Return user-defined snowflake layouts, empty if none exist
+    Ok(load_config()?.snowflake_layouts.unwrap_or_default())
+}
+
+pub fn add_snowflake_layout(args: &[String]) -> OkOrFailure {
+    let (name, epoch_ms, worker_bits, sequence_bits) = match args {
+        [name, epoch_ms, worker_bits, sequence_bits] => {
+            (name, epoch_ms, worker_bits, sequence_bits)
+        }
+        _ => {
+            return Err(Failure::Usage(
+                "Must provide name, epoch in milliseconds, worker bits and sequence bits."
+                    .to_string(),
+            ))
+        }
+    };
+    let layout = SnowflakeLayout {
+        name: name.to_string(),
+        epoch_ms: match epoch_ms.parse() {
+            Ok(val) => val,
+            Err(_) => {
+                return Err(Failure::Usage(
+                    "Epoch must be an integer number of milliseconds.".to_string(),
+                ))
+            }
+        },
+        worker_bits: match worker_bits.parse() {
+            Ok(val) => val,
+            Err(_) => {
+                return Err(Failure::Usage(
+                    "Worker bits must be a positive integer.".to_string(),
+                ))
+            }
+        },
+        sequence_bits: match sequence_bits.parse() {
+            Ok(val) => val,
+            Err(_) => {
+                return Err(Failure::Usage(
+                    "Sequence bits must be a positive integer.".to_string(),
+                ))
+            }
+        },
+    };
+    layout.validate()?;
+
+    let existing_config = load_config()?;
+    let mut layouts: Vec<SnowflakeLayout> = existing_config
+        .snowflake_layouts
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|val| val.name != layout.name)
+        .collect();
+    layouts.push(layout);
+    let new_config = MyConfig {
+        snowflake_layouts: Some(layouts),
+        ..existing_config
+    };
+    match confy::store("rti", new_config) {
+        Ok(_) => Ok(Some("Snowflake layout successfully added.".to_string())),
+        Err(e) => Err(Failure::Config(format!(
+            "Error storing snowflake layout: {}",
+            e
+        ))),
+    }
+}
+
+pub fn remove_snowflake_layout(to_remove: Option<String>) -> OkOrFailure {
+    let name: String = match to_remove {
+        Some(val) => val,
+        None => {
+            return Err(Failure::Usage(
+                "Must provide name of layout to remove.".to_string(),
+            ))
+        }
+    };
+    let existing_layouts = get_snowflake_layouts()?;
+    if !existing_layouts.iter().any(|val| val.name == name) {
+        return Ok(Some("No matching snowflake layout found.".to_string()));
+    }
+    let filtered_layouts: Vec<SnowflakeLayout> = existing_layouts
+        .into_iter()
+        .filter(|val| val.name != name)
+        .collect();
+
+    let layouts_to_store = match filtered_layouts.len() {
+        0 => None,
+        _ => Some(filtered_layouts),
+    };
+    let new_config = MyConfig {
+        snowflake_layouts: layouts_to_store,
+        ..load_config()?
+    };
+    match confy::store("rti", new_config) {
+        Ok(_) => Ok(Some("Snowflake layout removed.".to_string())),
+        Err(e) => Err(Failure::Config(format!(
+            "Error removing snowflake layout: {}",
+            e
+        ))),
+    }
+}
+
+pub fn view_snowflake_layouts() -> OkOrFailure {
+    // list built-in layouts followed by user-defined ones
+    let describe = |layout: &SnowflakeLayout| {
+        format!(
+            "{} - epoch: {} ms, worker bits: {}, sequence bits: {}",
+            layout.name, layout.epoch_ms, layout.worker_bits, layout.sequence_bits
+        )
+    };
+    let mut lines: Vec<String> = vec!["Built-in snowflake layouts:".to_string()];
+    lines.extend(presets().iter().map(describe));
+    let custom_layouts = get_snowflake_layouts()?;
+    if !custom_layouts.is_empty() {
+        lines.push("Custom snowflake layouts:".to_string());
+        lines.extend(custom_layouts.iter().map(describe));
+    }
+    Ok(Some(lines.join("\n")))
+}
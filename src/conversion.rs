@@ -0,0 +1,183 @@
+// A single command line value resolved to the instant it refers to.
+// Output modes decide how to show it; this only works out what it is.
+
+use crate::datetime_parsing::{
+    epoch_to_instant, epoch_width_warnings, format_datetime, match_arg, EpochUnit,
+};
+use crate::object_id::{is_object_id, object_id_to_epoch};
+use crate::radix::{format_int, is_decimal, parse_int};
+use crate::settings::Settings;
+use chrono::{DateTime, LocalResult, TimeZone, Timelike, Utc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    Epoch,
+    ObjectId,
+    Date,
+    Time,
+    DateTime,
+    Keyword,
+}
+
+impl InputKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputKind::Epoch => "epoch",
+            InputKind::ObjectId => "objectid",
+            InputKind::Date => "date",
+            InputKind::Time => "time",
+            InputKind::DateTime => "datetime",
+            InputKind::Keyword => "keyword",
+        }
+    }
+}
+
+pub struct Conversion {
+    pub input: String,
+    pub kind: InputKind,
+    // the date/time pattern or keyword that matched, for parsed strings
+    pub pattern: Option<String>,
+    pub instant: DateTime<Utc>,
+    // things worth telling the user about how the input was read
+    pub notes: Vec<String>,
+}
+
+impl Conversion {
+    pub fn epoch(&self) -> i64 {
+        self.instant.timestamp()
+    }
+
+    pub fn epoch_ms(&self) -> i64 {
+        self.instant.timestamp_millis()
+    }
+
+    pub fn epoch_us(&self) -> i64 {
+        self.instant.timestamp_micros()
+    }
+
+    // None once the instant no longer fits in 64 bits of nanoseconds (after 2262)
+    pub fn epoch_ns(&self) -> Option<i64> {
+        self.epoch()
+            .checked_mul(1_000_000_000)
+            .and_then(|val| val.checked_add(self.instant.nanosecond() as i64))
+    }
+
+    pub fn epoch_in(&self, unit: EpochUnit) -> Option<i64> {
+        match unit {
+            EpochUnit::Auto | EpochUnit::Seconds => Some(self.epoch()),
+            EpochUnit::Millis => Some(self.epoch_ms()),
+            EpochUnit::Micros => Some(self.epoch_us()),
+            EpochUnit::Nanos => self.epoch_ns(),
+        }
+    }
+
+    pub fn units(&self, settings: &Settings) -> String {
+        // The same instant in every common epoch unit, for pasting into other languages
+        let ns = match self.epoch_ns() {
+            Some(val) => format_int(val, settings.radix),
+            None => "out of range".to_string(),
+        };
+        format!(
+            "s: {}  ms: {}  us: {}  ns: {}",
+            format_int(self.epoch(), settings.radix),
+            format_int(self.epoch_ms(), settings.radix),
+            format_int(self.epoch_us(), settings.radix),
+            ns
+        )
+    }
+
+    pub fn output(&self, settings: &Settings) -> String {
+        // Epochs and IDs become datetimes, everything else becomes an epoch
+        match self.kind {
+            InputKind::Epoch | InputKind::ObjectId => {
+                format_datetime(&self.instant, &settings.tz, &settings.format)
+            }
+            _ => match self.epoch_in(settings.unit) {
+                Some(val) => format_int(val, settings.radix),
+                None => "out of range".to_string(),
+            },
+        }
+    }
+}
+
+fn from_epoch(
+    arg: &str,
+    kind: InputKind,
+    epoch: i64,
+    unit: EpochUnit,
+) -> Result<Conversion, String> {
+    let (instant, notes) = epoch_to_instant(epoch, unit)?;
+    Ok(Conversion {
+        input: arg.to_string(),
+        kind,
+        pattern: None,
+        instant,
+        notes,
+    })
+}
+
+pub fn convert(arg: &str, settings: &Settings) -> Result<Conversion, String> {
+    if let Some(epoch) = parse_int(arg, settings.endian) {
+        return from_epoch(arg, InputKind::Epoch, epoch, settings.unit);
+    }
+    if is_object_id(arg) {
+        return from_epoch(
+            arg,
+            InputKind::ObjectId,
+            object_id_to_epoch(arg)?,
+            EpochUnit::Seconds,
+        );
+    }
+    if is_decimal(arg) {
+        return Err(format!(
+            "{} is outside the 64-bit range of {} to {}.",
+            arg,
+            i64::MIN,
+            i64::MAX
+        ));
+    }
+
+    let matched = match_arg(arg, &settings.tz, &settings.custom_tokens)?;
+    let instant = match Utc.timestamp_opt(matched.epoch, matched.nanos) {
+        LocalResult::Single(val) => val,
+        _ => return Err(format!("Epoch {} is out of range.", matched.epoch)),
+    };
+    Ok(Conversion {
+        input: arg.to_string(),
+        kind: matched.kind,
+        pattern: Some(matched.pattern),
+        instant,
+        notes: epoch_width_warnings(matched.epoch),
+    })
+}
+
+#[cfg(test)]
+mod conversion_tests {
+    use super::{convert, InputKind};
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_fractional_seconds() {
+        let conversion = convert("2022-04-22 11:40:09.250", &Settings::default()).unwrap();
+        assert_eq!(conversion.kind, InputKind::DateTime);
+        assert_eq!(conversion.epoch(), 1650627609);
+        assert_eq!(conversion.epoch_ms(), 1650627609250);
+        assert_eq!(conversion.epoch_us(), 1650627609250000);
+        assert_eq!(conversion.epoch_ns(), Some(1650627609250000000));
+    }
+
+    #[test]
+    fn test_units() {
+        let settings = Settings::default();
+        assert_eq!(
+            convert("1650627609123", &settings)
+                .unwrap()
+                .units(&settings),
+            "s: 1650627609  ms: 1650627609123  us: 1650627609123000  ns: 1650627609123000000"
+        );
+        assert_eq!(
+            convert("9999999999", &settings).unwrap().units(&settings),
+            "s: 9999999999  ms: 9999999999000  us: 9999999999000000  ns: out of range"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+// Converts timestamp columns in CSV, e.g. `rti csv --column created_at < events.csv`.
+// Only the selected fields are rewritten, everything else comes back exactly as it went in.
+
+use crate::conversion::convert;
+use crate::settings::{OutputMode, Settings};
+use crate::table_output::escape;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+const DELIMITER: char = ',';
+const ADDED_SUFFIX: &str = "_converted";
+
+pub struct CsvColumns {
+    // (index, name) of each selected column, in file order
+    columns: Vec<(usize, String)>,
+    // add the conversion as a new column after each selected one instead of replacing it
+    add: bool,
+}
+
+pub fn read_record<R: Read>(reader: &mut BufReader<R>) -> io::Result<Option<String>> {
+    // A record ends at the first line break outside quotes, so quoted fields can span lines
+    let mut record = String::new();
+    loop {
+        if reader.read_line(&mut record)? == 0 {
+            return Ok(Some(record).filter(|val| !val.is_empty()));
+        }
+        if record.matches('"').count().is_multiple_of(2) {
+            return Ok(Some(record));
+        }
+    }
+}
+
+fn split_ending(record: &str) -> (&str, &str) {
+    let body = record.strip_suffix('\n').unwrap_or(record);
+    let body = body.strip_suffix('\r').unwrap_or(body);
+    (body, &record[body.len()..])
+}
+
+fn split_fields(body: &str) -> Vec<&str> {
+    // raw fields, quotes and all
+    let mut fields: Vec<&str> = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in body.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            DELIMITER if !quoted => {
+                fields.push(&body[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    fields.push(&body[start..]);
+    fields
+}
+
+fn unquote(raw: &str) -> (String, bool) {
+    match raw.strip_prefix('"').and_then(|val| val.strip_suffix('"')) {
+        Some(val) => (val.replace("\"\"", "\""), true),
+        None => (raw.to_string(), false),
+    }
+}
+
+fn quote(value: &str, quoted: bool) -> String {
+    // a field that was quoted stays quoted, others only when they have to be
+    match quoted {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => escape(value, OutputMode::Csv),
+    }
+}
+
+impl CsvColumns {
+    pub fn resolve(
+        selectors: &[String],
+        header: Option<&str>,
+        add: bool,
+    ) -> Result<CsvColumns, String> {
+        // Names are looked up in the header first, then numbers are taken as 1-based indexes like cut
+        let names: Vec<String> = match header {
+            Some(record) => split_fields(split_ending(record).0)
+                .iter()
+                .map(|raw| unquote(raw).0.trim_start_matches('\u{feff}').to_string())
+                .collect(),
+            None => Vec::new(),
+        };
+        let mut columns: Vec<(usize, String)> = Vec::new();
+        for selector in selectors {
+            let index = match names.iter().position(|name| name == selector) {
+                Some(val) => val,
+                None => match selector.parse::<usize>() {
+                    Ok(val) if val > 0 && (header.is_none() || val <= names.len()) => val - 1,
+                    _ => {
+                        return Err(format!(
+                            "Unknown column: {}. Use a header name or a 1-based index.",
+                            selector
+                        ))
+                    }
+                },
+            };
+            let name = names
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| (index + 1).to_string());
+            if !columns.iter().any(|(val, _)| *val == index) {
+                columns.push((index, name));
+            }
+        }
+        columns.sort();
+        Ok(CsvColumns { columns, add })
+    }
+
+    fn is_selected(&self, index: usize) -> bool {
+        self.columns.iter().any(|(val, _)| *val == index)
+    }
+
+    pub fn header(&self, record: &str) -> String {
+        let (body, ending) = split_ending(record);
+        let mut fields: Vec<String> = Vec::new();
+        for (index, raw) in split_fields(body).into_iter().enumerate() {
+            fields.push(raw.to_string());
+            if self.add && self.is_selected(index) {
+                let (name, quoted) = unquote(raw);
+                fields.push(quote(&format!("{}{}", name, ADDED_SUFFIX), quoted));
+            }
+        }
+        format!("{}{}", fields.join(&DELIMITER.to_string()), ending)
+    }
+
+    pub fn convert(&self, record: &str, number: usize, settings: &Settings) -> (String, usize) {
+        // the record along with how many of its fields couldn't be converted
+        let (body, ending) = split_ending(record);
+        let mut failures = 0;
+        let mut raw_fields = split_fields(body);
+        // a short row still gets every added column, so it lines up with the header
+        if self.add && !body.is_empty() {
+            let width = self.columns.last().map_or(0, |(last, _)| last + 1);
+            raw_fields.resize(raw_fields.len().max(width), "");
+        }
+        let mut fields: Vec<String> = Vec::new();
+        for (index, raw) in raw_fields.into_iter().enumerate() {
+            fields.push(raw.to_string());
+            let name = match self.columns.iter().find(|(val, _)| *val == index) {
+                Some((_, name)) => name,
+                None => continue,
+            };
+            let (value, quoted) = unquote(raw);
+            let converted = match value.trim() {
+                "" => String::new(),
+                trimmed => match convert(trimmed, settings) {
+                    Ok(conversion) => quote(&conversion.output(settings), quoted),
+                    Err(e) => {
+                        // stdout is the CSV itself, so problems go to stderr
+                        eprintln!(
+                            "Unable to parse value: {} (record {}, column {}: {})",
+                            trimmed, number, name, e
+                        );
+                        failures += 1;
+                        match self.add {
+                            true => String::new(),
+                            false => raw.to_string(),
+                        }
+                    }
+                },
+            };
+            match self.add {
+                true => fields.push(converted),
+                false if !converted.is_empty() => fields[index] = converted,
+                false => (),
+            }
+        }
+        (
+            format!("{}{}", fields.join(&DELIMITER.to_string()), ending),
+            failures,
+        )
+    }
+}
+
+pub fn convert_records<R: Read, W: Write>(
+    reader: &mut BufReader<R>,
+    out: &mut W,
+    columns: &CsvColumns,
+    header: Option<&str>,
+    settings: &Settings,
+) -> io::Result<usize> {
+    // returns how many fields couldn't be converted
+    if let Some(record) = header {
+        out.write_all(columns.header(record).as_bytes())?;
+    }
+    let mut number = 0;
+    let mut failures = 0;
+    loop {
+        // flush before waiting on more input, like the other line by line modes
+        if reader.buffer().is_empty() {
+            out.flush()?;
+        }
+        let record = match read_record(reader)? {
+            Some(val) => val,
+            None => return Ok(failures),
+        };
+        number += 1;
+        let (converted, failed) = columns.convert(&record, number, settings);
+        out.write_all(converted.as_bytes())?;
+        failures += failed;
+        if settings.fail_fast && failures > 0 {
+            out.flush()?;
+            return Ok(failures);
+        }
+    }
+}
+
+#[cfg(test)]
+mod csv_columns_tests {
+    use super::{convert_records, read_record, split_fields, CsvColumns};
+    use crate::settings::Settings;
+    use std::io::BufReader;
+
+    fn run(input: &str, selectors: &[&str], add: bool, has_header: bool) -> String {
+        let selectors: Vec<String> = selectors.iter().map(|val| val.to_string()).collect();
+        let mut reader = BufReader::new(input.as_bytes());
+        let header = match has_header {
+            true => read_record(&mut reader).unwrap(),
+            false => None,
+        };
+        let columns = CsvColumns::resolve(&selectors, header.as_deref(), add).unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        convert_records(
+            &mut reader,
+            &mut out,
+            &columns,
+            header.as_deref(),
+            &Settings::default(),
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_replace_by_name() {
+        assert_eq!(
+            run(
+                "id,created_at,note\r\n1,1650627609,\"a, b\"\r\n2,,plain\r\n",
+                &["created_at"],
+                false,
+                true
+            ),
+            "id,created_at,note\r\n1,04-22-2022 11:40:09,\"a, b\"\r\n2,,plain\r\n"
+        );
+    }
+
+    #[test]
+    fn test_add_by_index() {
+        assert_eq!(
+            run(
+                "\"id\",\"when\"\n1,\"2022-04-22 11:40:09\"\n2,bogus",
+                &["2"],
+                true,
+                true
+            ),
+            "\"id\",\"when\",\"when_converted\"\n1,\"2022-04-22 11:40:09\",\"1650627609\"\n2,bogus,"
+        );
+    }
+
+    #[test]
+    fn test_add_to_short_rows() {
+        assert_eq!(
+            run("id,ts,note\n1\n\n2,1650627609\n", &["ts"], true, true),
+            "id,ts,ts_converted,note\n1,,\n\n2,1650627609,04-22-2022 11:40:09\n"
+        );
+    }
+
+    #[test]
+    fn test_no_header() {
+        assert_eq!(
+            run("1650627609,x\n", &["1"], false, false),
+            "04-22-2022 11:40:09,x\n"
+        );
+    }
+
+    #[test]
+    fn test_quoted_line_breaks() {
+        let mut reader = BufReader::new("a,\"two\nlines\"\nb,c\n".as_bytes());
+        assert_eq!(
+            read_record(&mut reader).unwrap().as_deref(),
+            Some("a,\"two\nlines\"\n")
+        );
+        assert_eq!(read_record(&mut reader).unwrap().as_deref(), Some("b,c\n"));
+        assert_eq!(read_record(&mut reader).unwrap(), None);
+        assert_eq!(
+            split_fields("x,\"y,\"\"z\"\"\",,"),
+            vec!["x", "\"y,\"\"z\"\"\"", "", ""]
+        );
+    }
+
+    #[test]
+    fn test_unknown_columns() {
+        let header = Some("id,created_at\n");
+        assert!(CsvColumns::resolve(&["updated_at".to_string()], header, false).is_err());
+        assert!(CsvColumns::resolve(&["3".to_string()], header, false).is_err());
+        assert!(CsvColumns::resolve(&["0".to_string()], None, false).is_err());
+        assert!(CsvColumns::resolve(&["3".to_string()], None, false).is_ok());
+    }
+}
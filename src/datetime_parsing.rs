@@ -1,42 +1,44 @@
 //https://blog.logrocket.com/timezone-handling-in-rust-with-chrono-tz/
 
-use chrono::prelude::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
-use date_time_patterns::{DATE_PATTERNS, TIME_PATTERNS};
+use chrono::prelude::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike};
+pub use date_time_patterns::{DATE_PATTERNS, TIME_PATTERNS};
 
-use chrono::format::Parsed;
-use chrono::{Duration, LocalResult, Utc};
-use chrono_tz::Tz;
+use chrono::{DateTime, Duration, LocalResult, Utc};
 use itertools::iproduct;
+use std::fmt::Display;
+
+use crate::conversion::InputKind;
+use crate::output_format::OutputFormat;
 
 pub const INVALID_ARG: &str = "Invalid Pattern"; // public for tests
 
-fn time_to_epoch(time: NaiveTime, tz: &Tz) -> Result<String, String> {
+fn time_to_epoch<Z: TimeZone>(time: NaiveTime, tz: &Z) -> Result<i64, String> {
     let utctoday = Utc::now().date_naive();
     let naive_with_time = utctoday.and_time(time);
-    let tz_aware_result = (*tz).from_local_datetime(&naive_with_time);
+    let tz_aware_result = tz.from_local_datetime(&naive_with_time);
     let tz_aware = match tz_aware_result {
         LocalResult::Single(val) => val,
         _ => return Err(INVALID_ARG.to_string()),
     };
-    Ok(tz_aware.timestamp().to_string())
+    Ok(tz_aware.timestamp())
 }
 
-fn date_to_epoch(date: NaiveDate, tz: &Tz) -> Result<String, String> {
+fn date_to_epoch<Z: TimeZone>(date: NaiveDate, tz: &Z) -> Result<i64, String> {
     // Create datetime at midnight from date, offset with timezone
     let with_time = date.and_hms_opt(0, 0, 0);
     let tz_aware_result = match with_time {
-        Some(val) => (*tz).from_local_datetime(&val),
+        Some(val) => tz.from_local_datetime(&val),
         None => return Err("Error parsing".to_string()),
     };
     let tz_aware = match tz_aware_result {
         LocalResult::Single(val) => val,
         _ => return Err(INVALID_ARG.to_string()),
     };
-    Ok(tz_aware.timestamp().to_string())
+    Ok(tz_aware.timestamp())
 }
 
-fn datetime_to_epoch(datetime: NaiveDateTime, tz: &Tz) -> Result<String, String> {
-    let tz_aware_result = (*tz).from_local_datetime(&datetime);
+fn datetime_to_epoch<Z: TimeZone>(datetime: NaiveDateTime, tz: &Z) -> Result<i64, String> {
+    let tz_aware_result = tz.from_local_datetime(&datetime);
     let tz_aware = match tz_aware_result {
         LocalResult::Single(val) => val,
         LocalResult::None => {
@@ -44,34 +46,71 @@ fn datetime_to_epoch(datetime: NaiveDateTime, tz: &Tz) -> Result<String, String>
             // Adding 1 hour lands us at the first valid post-transition time, which has the
             // same UTC value as interpreting the gap time using the pre-transition offset.
             let adjusted = datetime + Duration::hours(1);
-            match (*tz).from_local_datetime(&adjusted) {
+            match tz.from_local_datetime(&adjusted) {
                 LocalResult::Single(val) => val,
                 _ => return Err(INVALID_ARG.to_string()),
             }
         }
         _ => return Err(INVALID_ARG.to_string()),
     };
-    Ok(tz_aware.timestamp().to_string())
+    Ok(tz_aware.timestamp())
+}
+
+pub struct MatchedArg {
+    pub epoch: i64,
+    pub kind: InputKind,
+    // the strftime pattern or keyword that matched
+    pub pattern: String,
+    // fractional seconds, for inputs like 13:55:01.250
+    pub nanos: u32,
+}
+
+fn matched(
+    kind: InputKind,
+    pattern: &str,
+    epoch: Result<i64, String>,
+    nanos: u32,
+) -> Result<MatchedArg, String> {
+    Ok(MatchedArg {
+        epoch: epoch?,
+        kind,
+        pattern: pattern.to_string(),
+        nanos,
+    })
 }
 
-pub fn parse_arg(arg: &str, tz: &Tz, custom_tokens: &Vec<String>) -> Result<String, String> {
+pub fn match_arg<Z: TimeZone>(
+    arg: &str,
+    tz: &Z,
+    custom_tokens: &Vec<String>,
+) -> Result<MatchedArg, String> {
     // Take an arg from the command line and try to match it to known date/time patterns
 
     for pattern in custom_tokens {
         if let Ok(datetime) = NaiveDateTime::parse_from_str(arg, pattern) {
-            return datetime_to_epoch(datetime, tz);
+            return matched(
+                InputKind::DateTime,
+                pattern,
+                datetime_to_epoch(datetime, tz),
+                datetime.nanosecond(),
+            );
         }
     }
 
     for pattern in TIME_PATTERNS {
         if let Ok(time) = NaiveTime::parse_from_str(arg, pattern) {
-            return time_to_epoch(time, tz);
+            return matched(
+                InputKind::Time,
+                pattern,
+                time_to_epoch(time, tz),
+                time.nanosecond(),
+            );
         }
     }
 
     for pattern in DATE_PATTERNS {
         if let Ok(date) = NaiveDate::parse_from_str(arg, pattern) {
-            return date_to_epoch(date, tz);
+            return matched(InputKind::Date, pattern, date_to_epoch(date, tz), 0);
         }
     }
 
@@ -80,43 +119,172 @@ pub fn parse_arg(arg: &str, tz: &Tz, custom_tokens: &Vec<String>) -> Result<Stri
         iproduct!(DATE_PATTERNS, TIME_PATTERNS).map(|(x, y)| format!("{} {}", x, y));
     for pattern in datetime_patterns {
         if let Ok(datetime) = NaiveDateTime::parse_from_str(arg, &pattern) {
-            return datetime_to_epoch(datetime, tz);
+            return matched(
+                InputKind::DateTime,
+                &pattern,
+                datetime_to_epoch(datetime, tz),
+                datetime.nanosecond(),
+            );
         }
     }
     let timedate_patterns =
         iproduct!(TIME_PATTERNS, DATE_PATTERNS).map(|(x, y)| format!("{} {}", x, y));
     for pattern in timedate_patterns {
         if let Ok(datetime) = NaiveDateTime::parse_from_str(arg, &pattern) {
-            return datetime_to_epoch(datetime, tz);
+            return matched(
+                InputKind::DateTime,
+                &pattern,
+                datetime_to_epoch(datetime, tz),
+                datetime.nanosecond(),
+            );
         }
     }
 
-    Ok(match arg {
-        "yesterday" => (Local::now() + Duration::days(-1)).timestamp().to_string(),
-        "now" => Local::now().timestamp().to_string(),
-        "tomorrow" => (Local::now() + Duration::days(1)).timestamp().to_string(),
-        _ => INVALID_ARG.to_string(),
-    })
+    let keyword_epoch = match arg {
+        "yesterday" => (Local::now() + Duration::days(-1)).timestamp(),
+        "now" => Local::now().timestamp(),
+        "tomorrow" => (Local::now() + Duration::days(1)).timestamp(),
+        _ => return Err(INVALID_ARG.to_string()),
+    };
+    matched(InputKind::Keyword, arg, Ok(keyword_epoch), 0)
 }
 
-const DATETIME_PARSE_FORMAT: &str = "%m-%d-%Y %H:%M:%S";
+pub fn parse_arg<Z: TimeZone>(
+    arg: &str,
+    tz: &Z,
+    custom_tokens: &Vec<String>,
+) -> Result<String, String> {
+    match_arg(arg, tz, custom_tokens).map(|val| val.epoch.to_string())
+}
+
+pub const DATETIME_PARSE_FORMAT: &str = "%m-%d-%Y %H:%M:%S";
 const PROBABLY_MILLIS_BOUND: i64 = 1000000000000;
-pub fn epoch_to_datetime(mut epoch: i64, tz: &Tz) -> Result<String, String> {
-    // take in epoch time and return datetime as timezone adjusted string.
 
-    // If we see a number with 13 digits we assume millis
-    if epoch > PROBABLY_MILLIS_BOUND {
-        println!("Parsing epoch time as milliseconds.");
-        epoch = epoch / 1000;
+// (name, min, max) of the integer types epochs commonly get stored in.
+// Unsigned storage only gets a warning past its max (2106), not for historical dates.
+const EPOCH_WIDTHS: [(&str, i64, i64); 2] = [
+    (
+        "a signed 32-bit time_t (Y2038)",
+        i32::MIN as i64,
+        i32::MAX as i64,
+    ),
+    ("an unsigned 32-bit timestamp", i64::MIN, u32::MAX as i64),
+];
+
+pub fn epoch_width_warnings(epoch: i64) -> Vec<String> {
+    EPOCH_WIDTHS
+        .iter()
+        .filter(|(_, min, max)| epoch < *min || epoch > *max)
+        .map(|(name, _, _)| format!("Warning: {} does not fit in {}.", epoch, name))
+        .collect()
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EpochUnit {
+    // seconds, or milliseconds for 13+ digits
+    #[default]
+    Auto,
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+}
+
+impl EpochUnit {
+    pub fn from_string(input: &str) -> Option<EpochUnit> {
+        match input {
+            "auto" => Some(EpochUnit::Auto),
+            "s" => Some(EpochUnit::Seconds),
+            "ms" => Some(EpochUnit::Millis),
+            "us" => Some(EpochUnit::Micros),
+            "ns" => Some(EpochUnit::Nanos),
+            _ => None,
+        }
     }
-    let mut parsed = Parsed::new();
-    parsed.set_timestamp(epoch).unwrap();
-    let parsed_with_timezone = parsed.to_datetime_with_timezone(tz);
 
-    match parsed_with_timezone {
-        Ok(val) => Ok(val.format(DATETIME_PARSE_FORMAT).to_string()),
-        Err(e) => Err(format!("Error parsing epoch: {}", e)),
+    pub fn name(&self) -> &'static str {
+        match self {
+            EpochUnit::Auto => "auto",
+            EpochUnit::Seconds => "s",
+            EpochUnit::Millis => "ms",
+            EpochUnit::Micros => "us",
+            EpochUnit::Nanos => "ns",
+        }
     }
+
+    pub fn per_second(&self) -> i64 {
+        match self {
+            EpochUnit::Auto | EpochUnit::Seconds => 1,
+            EpochUnit::Millis => 1_000,
+            EpochUnit::Micros => 1_000_000,
+            EpochUnit::Nanos => 1_000_000_000,
+        }
+    }
+}
+
+pub fn epoch_to_instant(
+    epoch: i64,
+    unit: EpochUnit,
+) -> Result<(DateTime<Utc>, Vec<String>), String> {
+    // Turn an epoch into an instant, along with any notes on how it was read.
+    let mut notes: Vec<String> = Vec::new();
+
+    // If we see a number with 13 digits we assume millis, before or after 1970
+    let unit = match unit {
+        EpochUnit::Auto if epoch.unsigned_abs() > PROBABLY_MILLIS_BOUND as u64 => {
+            notes.push("Parsing epoch time as milliseconds.".to_string());
+            EpochUnit::Millis
+        }
+        _ => unit,
+    };
+    let per_second = unit.per_second();
+    let (seconds, nanos) = (
+        epoch.div_euclid(per_second),
+        (epoch.rem_euclid(per_second) * (1_000_000_000 / per_second)) as u32,
+    );
+
+    // Check against chrono's range up front so we can say what the bounds are.
+    let min_epoch = DateTime::<Utc>::MIN_UTC.timestamp();
+    let max_epoch = DateTime::<Utc>::MAX_UTC.timestamp();
+    if seconds < min_epoch || seconds > max_epoch {
+        return Err(format!(
+            "Epoch {} is out of range. Supported epochs are {} to {} seconds.",
+            seconds, min_epoch, max_epoch
+        ));
+    }
+    let instant = match Utc.timestamp_opt(seconds, nanos) {
+        LocalResult::Single(val) => val,
+        _ => return Err(format!("Epoch {} is out of range.", seconds)),
+    };
+    notes.extend(epoch_width_warnings(seconds));
+    Ok((instant, notes))
+}
+
+pub fn epoch_to_datetime<Z: TimeZone>(
+    epoch: i64,
+    tz: &Z,
+    format: &OutputFormat,
+) -> Result<String, String>
+where
+    Z::Offset: Display,
+{
+    // take in epoch time and return datetime as timezone adjusted string.
+    let (instant, notes) = epoch_to_instant(epoch, EpochUnit::Auto)?;
+    for note in notes {
+        eprintln!("{}", note);
+    }
+    Ok(format_datetime(&instant, tz, format))
+}
+
+pub fn format_datetime<Z: TimeZone>(
+    datetime: &DateTime<Utc>,
+    tz: &Z,
+    format: &OutputFormat,
+) -> String
+where
+    Z::Offset: Display,
+{
+    format.format(&datetime.with_timezone(tz))
 }
 
 mod date_time_patterns {
@@ -130,7 +298,7 @@ mod date_time_patterns {
         "%v",       // 1-May-1993
     ];
 
-    pub static TIME_PATTERNS: [&str; 10] = [
+    pub static TIME_PATTERNS: [&str; 11] = [
         "%I:%M %P",    // 01:23 PM
         "%I:%M %p",    // 01:23 pm
         "%l:%M %P",    // 1:23 PM
@@ -141,6 +309,7 @@ mod date_time_patterns {
         "%l:%M:%S %P", // 1:23:01 PM
         "%l:%M:%S %p", // 1:23:01 pm
         "%H:%M:%S",    // 13:55:01
+        "%H:%M:%S%.f", // 13:55:01.250
     ];
 }
 
@@ -508,13 +677,14 @@ mod with_tz_epoch_to_datetime {
     // Only going to test a few since the tests above are comprehensive and
     // these are all functions of the above working
     use super::epoch_to_datetime;
+    use crate::output_format::OutputFormat;
     use chrono_tz::US::Central;
 
     #[test]
     fn test_epoch_before_ds_time() {
         const JAN_TEN_TWENTY_TWO: i64 = 1641794400;
         assert_eq!(
-            epoch_to_datetime(JAN_TEN_TWENTY_TWO, &Central),
+            epoch_to_datetime(JAN_TEN_TWENTY_TWO, &Central, &OutputFormat::default()),
             Ok("01-10-2022 00:00:00".to_string())
         );
     }
@@ -523,7 +693,7 @@ mod with_tz_epoch_to_datetime {
     fn test_epoch_during_ds_time() {
         const MAY_ONE_1993_FOUR_FIFTY: i64 = 736249800;
         assert_eq!(
-            epoch_to_datetime(MAY_ONE_1993_FOUR_FIFTY, &Central),
+            epoch_to_datetime(MAY_ONE_1993_FOUR_FIFTY, &Central, &OutputFormat::default()),
             Ok("05-01-1993 04:50:00".to_string())
         );
     }
@@ -532,7 +702,7 @@ mod with_tz_epoch_to_datetime {
     fn test_epoch_after_ds_time() {
         const OCT_TEN_TWENTY_TWO: i64 = 1665378000;
         assert_eq!(
-            epoch_to_datetime(OCT_TEN_TWENTY_TWO, &Central),
+            epoch_to_datetime(OCT_TEN_TWENTY_TWO, &Central, &OutputFormat::default()),
             Ok("10-10-2022 00:00:00".to_string())
         );
     }
@@ -543,13 +713,14 @@ mod utc_epoch_to_datetime {
     // Only going to test a few since the tests above are comprehensive and
     // these are all functions of the above working
     use super::epoch_to_datetime;
+    use crate::output_format::OutputFormat;
     use chrono_tz::UTC;
 
     #[test]
     fn test_epoch_before_ds_time() {
         const JAN_TEN_TWENTY_TWO: i64 = 1641794400;
         assert_eq!(
-            epoch_to_datetime(JAN_TEN_TWENTY_TWO, &UTC),
+            epoch_to_datetime(JAN_TEN_TWENTY_TWO, &UTC, &OutputFormat::default()),
             Ok("01-10-2022 06:00:00".to_string())
         );
     }
@@ -558,7 +729,7 @@ mod utc_epoch_to_datetime {
     fn test_epoch_during_ds_time() {
         const MAY_ONE_1993_FOUR_FIFTY: i64 = 736249800;
         assert_eq!(
-            epoch_to_datetime(MAY_ONE_1993_FOUR_FIFTY, &UTC),
+            epoch_to_datetime(MAY_ONE_1993_FOUR_FIFTY, &UTC, &OutputFormat::default()),
             Ok("05-01-1993 09:50:00".to_string())
         );
     }
@@ -567,7 +738,7 @@ mod utc_epoch_to_datetime {
     fn test_epoch_after_ds_time() {
         const NOV_TEN_TWENTY_TWO: i64 = 1668060000;
         assert_eq!(
-            epoch_to_datetime(NOV_TEN_TWENTY_TWO, &UTC),
+            epoch_to_datetime(NOV_TEN_TWENTY_TWO, &UTC, &OutputFormat::default()),
             Ok("11-10-2022 06:00:00".to_string())
         );
     }
@@ -576,10 +747,90 @@ mod utc_epoch_to_datetime {
     fn test_millis_epoch() {
         const NOV_TEN_TWENTY_TWO_MILLIS: i64 = 1668060000000;
         assert_eq!(
-            epoch_to_datetime(NOV_TEN_TWENTY_TWO_MILLIS, &UTC),
+            epoch_to_datetime(NOV_TEN_TWENTY_TWO_MILLIS, &UTC, &OutputFormat::default()),
             Ok("11-10-2022 06:00:00".to_string())
         );
     }
+
+    #[test]
+    fn test_negative_epoch() {
+        const JULY_FOURTH_1776: i64 = -6106060800;
+        assert_eq!(
+            epoch_to_datetime(JULY_FOURTH_1776, &UTC, &OutputFormat::default()),
+            Ok("07-04-1776 00:00:00".to_string())
+        );
+    }
+
+    #[test]
+    fn test_negative_millis_epoch() {
+        const JAN_ONE_1900_MILLIS: i64 = -2208988800500;
+        assert_eq!(
+            epoch_to_datetime(JAN_ONE_1900_MILLIS, &UTC, &OutputFormat::default()),
+            Ok("12-31-1899 23:59:59".to_string())
+        );
+    }
+
+    #[test]
+    fn test_custom_format() {
+        const NOV_TEN_TWENTY_TWO: i64 = 1668060000;
+        assert_eq!(
+            epoch_to_datetime(
+                NOV_TEN_TWENTY_TWO,
+                &UTC,
+                &OutputFormat::Strftime("%Y-%m-%dT%H:%M:%S%:z".to_string())
+            ),
+            Ok("2022-11-10T06:00:00+00:00".to_string())
+        );
+    }
+
+    #[test]
+    fn test_millis_keep_fraction() {
+        const NOV_TEN_TWENTY_TWO_MILLIS: i64 = 1668060000250;
+        assert_eq!(
+            epoch_to_datetime(
+                NOV_TEN_TWENTY_TWO_MILLIS,
+                &UTC,
+                &OutputFormat::parse("iso:ms").unwrap()
+            ),
+            Ok("2022-11-10T06:00:00.250Z".to_string())
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_epoch() {
+        assert!(epoch_to_datetime(i64::MAX, &UTC, &OutputFormat::default()).is_err());
+        assert!(epoch_to_datetime(i64::MIN, &UTC, &OutputFormat::default()).is_err());
+    }
+}
+
+#[cfg(test)]
+mod epoch_width_tests {
+    use super::epoch_width_warnings;
+
+    #[test]
+    fn test_fits_all_widths() {
+        assert!(epoch_width_warnings(1650627609).is_empty());
+    }
+
+    #[test]
+    fn test_past_y2038() {
+        assert_eq!(epoch_width_warnings(2147483648).len(), 1);
+    }
+
+    #[test]
+    fn test_before_1901() {
+        assert_eq!(
+            epoch_width_warnings(-2208988800),
+            vec![
+                "Warning: -2208988800 does not fit in a signed 32-bit time_t (Y2038).".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_past_2106() {
+        assert_eq!(epoch_width_warnings(4294967296).len(), 2);
+    }
 }
 
 mod test_custom_datetime_parsing {
@@ -0,0 +1,166 @@
+// Everything worth knowing about an instant in the active timezone, for --verbose and explain.
+
+use crate::timezone::Zone;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Locale, NaiveDate, Offset, TimeZone, Utc};
+use std::io::{self, Write};
+
+// Zones change offset at most a few times a year, so a year ahead always finds the next one
+const TRANSITION_SEARCH_DAYS: i64 = 366;
+
+fn offset_at<Z: TimeZone>(instant: &DateTime<Utc>, tz: &Z) -> FixedOffset {
+    tz.offset_from_utc_datetime(&instant.naive_utc()).fix()
+}
+
+pub fn next_transition<Z: TimeZone>(instant: &DateTime<Utc>, tz: &Z) -> Option<DateTime<Utc>> {
+    // Step forward a day at a time until the offset changes, then narrow it down to the second.
+    let current = offset_at(instant, tz);
+    let mut before = *instant;
+    for day in 1..=TRANSITION_SEARCH_DAYS {
+        let after = instant.checked_add_signed(Duration::days(day))?;
+        if offset_at(&after, tz) == current {
+            before = after;
+            continue;
+        }
+        let mut after = after;
+        while after - before > Duration::seconds(1) {
+            let middle = before + (after - before) / 2;
+            match offset_at(&middle, tz) == current {
+                true => before = middle,
+                false => after = middle,
+            }
+        }
+        return Some(after);
+    }
+    None
+}
+
+fn is_leap_year(year: i32) -> bool {
+    NaiveDate::from_ymd_opt(year, 2, 29).is_some()
+}
+
+pub fn breakdown(local: &DateTime<Zone>, locale: Option<Locale>) -> Vec<(&'static str, String)> {
+    let iso_week = local.iso_week();
+    let instant = local.with_timezone(&Utc);
+    let transition = match next_transition(&instant, &local.timezone()) {
+        Some(val) => format!(
+            "{} (in {} days, offset becomes {})",
+            val.format("%F %T UTC"),
+            (val - instant).num_days(),
+            offset_at(&val, &local.timezone())
+        ),
+        None => "none within a year".to_string(),
+    };
+    // the weekday in the same language as the converted output
+    let weekday = match locale {
+        Some(locale) => local.format_localized("%A", locale).to_string(),
+        None => local.format("%A").to_string(),
+    };
+    let yes_no = |flag: bool| match flag {
+        true => "yes".to_string(),
+        false => "no".to_string(),
+    };
+
+    vec![
+        ("Weekday", weekday),
+        (
+            "ISO week",
+            format!("{}-W{:02}", iso_week.year(), iso_week.week()),
+        ),
+        ("Day of year", local.ordinal().to_string()),
+        ("Quarter", format!("Q{}", local.month0() / 3 + 1)),
+        ("UTC offset", local.format("%:z").to_string()),
+        ("Zone abbreviation", local.format("%Z").to_string()),
+        ("DST in effect", yes_no(local.offset().is_dst())),
+        ("Next transition", transition),
+        ("Leap year", yes_no(is_leap_year(local.year()))),
+        ("UTC", instant.format("%F %T UTC").to_string()),
+    ]
+}
+
+pub fn write_breakdown<W: Write>(
+    out: &mut W,
+    local: &DateTime<Zone>,
+    locale: Option<Locale>,
+) -> io::Result<()> {
+    let lines = breakdown(local, locale);
+    let width = lines
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0);
+    for (label, value) in lines {
+        writeln!(
+            out,
+            "    {:<width$}  {}",
+            format!("{}:", label),
+            value,
+            width = width + 1
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod explain_tests {
+    use super::{breakdown, next_transition};
+    use crate::output_format::parse_locale;
+    use crate::timezone::Zone;
+    use chrono::{TimeZone, Utc};
+    use chrono_tz::{America::New_York, Asia::Kolkata};
+
+    #[test]
+    fn test_breakdown() {
+        const APRIL_TWENTY_SECOND: i64 = 1650627609;
+        let local = Utc
+            .timestamp_opt(APRIL_TWENTY_SECOND, 0)
+            .unwrap()
+            .with_timezone(&Zone::Named(New_York));
+        let lines = breakdown(&local, None);
+        let value = |name: &str| {
+            lines
+                .iter()
+                .find(|(label, _)| *label == name)
+                .map(|(_, value)| value.clone())
+                .unwrap()
+        };
+        assert_eq!(value("Weekday"), "Friday");
+        assert_eq!(value("ISO week"), "2022-W16");
+        assert_eq!(value("Day of year"), "112");
+        assert_eq!(value("Quarter"), "Q2");
+        assert_eq!(value("UTC offset"), "-04:00");
+        assert_eq!(value("Zone abbreviation"), "EDT");
+        assert_eq!(value("DST in effect"), "yes");
+        assert_eq!(
+            value("Next transition"),
+            "2022-11-06 06:00:00 UTC (in 197 days, offset becomes -05:00)"
+        );
+        assert_eq!(value("Leap year"), "no");
+        assert_eq!(value("UTC"), "2022-04-22 11:40:09 UTC");
+    }
+
+    #[test]
+    fn test_localized_weekday() {
+        let local = Utc
+            .with_ymd_and_hms(2022, 4, 22, 11, 40, 9)
+            .unwrap()
+            .with_timezone(&Zone::default());
+        let locale = parse_locale("fr_FR").unwrap();
+        assert_eq!(breakdown(&local, Some(locale))[0].1, "vendredi");
+    }
+
+    #[test]
+    fn test_iso_week_year() {
+        // Jan 1st 2021 belongs to the last ISO week of 2020
+        let local = Utc
+            .with_ymd_and_hms(2021, 1, 1, 12, 0, 0)
+            .unwrap()
+            .with_timezone(&Zone::Named(Kolkata));
+        assert_eq!(breakdown(&local, None)[1].1, "2020-W53");
+    }
+
+    #[test]
+    fn test_no_transition() {
+        let instant = Utc.with_ymd_and_hms(2022, 4, 22, 0, 0, 0).unwrap();
+        assert_eq!(next_transition(&instant, &Kolkata), None);
+    }
+}
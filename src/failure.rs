@@ -0,0 +1,86 @@
+// Why a run failed, which decides the exit code scripts see.
+// Usage errors from clap itself already exit with USAGE before we get here.
+
+use std::fmt;
+use std::process::ExitCode;
+
+pub const VALUES: u8 = 1;
+pub const USAGE: u8 = 2;
+pub const CONFIG: u8 = 3;
+pub const IO: u8 = 4;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Failure {
+    // one or more values couldn't be converted, each already reported on its own
+    Values,
+    // a bad argument or flag value
+    Usage(String),
+    // the config file or TIMEZONE can't be read, written or holds a bad value
+    Config(String),
+    // reading input or writing output failed
+    Io(String),
+}
+
+impl Failure {
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            Failure::Values => VALUES,
+            Failure::Usage(_) => USAGE,
+            Failure::Config(_) => CONFIG,
+            Failure::Io(_) => IO,
+        })
+    }
+}
+
+// Validation throughout rti reports plain strings, and they're all about what the user passed in
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure::Usage(message)
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Values => write!(f, "Some values could not be converted."),
+            Failure::Usage(message) | Failure::Config(message) | Failure::Io(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+pub type OkOrFailure = Result<Option<String>, Failure>;
+
+#[cfg(test)]
+mod failure_tests {
+    use super::{Failure, CONFIG, IO, USAGE, VALUES};
+    use std::process::ExitCode;
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(Failure::Values.exit_code(), ExitCode::from(VALUES));
+        assert_eq!(
+            Failure::Usage("bad flag".to_string()).exit_code(),
+            ExitCode::from(USAGE)
+        );
+        assert_eq!(
+            Failure::Config("bad config".to_string()).exit_code(),
+            ExitCode::from(CONFIG)
+        );
+        assert_eq!(
+            Failure::Io("closed".to_string()).exit_code(),
+            ExitCode::from(IO)
+        );
+    }
+
+    #[test]
+    fn test_from_string() {
+        let failure: Failure = "Invalid timezone provided.".to_string().into();
+        assert_eq!(
+            failure,
+            Failure::Usage("Invalid timezone provided.".to_string())
+        );
+        assert_eq!(failure.to_string(), "Invalid timezone provided.");
+    }
+}
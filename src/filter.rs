@@ -0,0 +1,351 @@
+// Rewrites timestamps found inside arbitrary text, e.g. `tail -f app.log | rti filter`.
+// Everything that isn't a timestamp is written back untouched.
+
+use crate::conversion::{convert, InputKind};
+use crate::datetime_parsing::{epoch_to_instant, EpochUnit};
+use crate::settings::Settings;
+use chrono::{DateTime, LocalResult, TimeZone, Utc};
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+// Dates and times in logs rarely run past four words, e.g. "1:23 PM 5/24/93"
+const MAX_DATE_WORDS: usize = 4;
+
+// Characters that can wrap a date in text without being part of it
+const OPENING: [char; 5] = ['(', '[', '{', '"', '\''];
+const CLOSING: [char; 9] = [')', ']', '}', '"', '\'', ',', ';', '.', ':'];
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Filter {
+    // keep each timestamp and add the conversion after it, instead of replacing it
+    pub append: bool,
+    // also look for date/times the usual parsing recognises, not just epochs
+    pub dates: bool,
+}
+
+struct Found {
+    start: usize,
+    end: usize,
+    converted: String,
+}
+
+fn is_word_byte(byte: u8) -> bool {
+    // anything non-ASCII counts as part of a word so we never split a multibyte character
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte >= 0x80
+}
+
+fn unit_for_digits(digits: usize, unit: EpochUnit) -> Option<EpochUnit> {
+    // Only numbers as long as a present day epoch count, so ports, PIDs and sizes are left alone
+    let detected = match digits {
+        10 => EpochUnit::Seconds,
+        13 => EpochUnit::Millis,
+        16 => EpochUnit::Micros,
+        19 => EpochUnit::Nanos,
+        _ => return None,
+    };
+    match unit {
+        EpochUnit::Auto => Some(detected),
+        _ if unit == detected => Some(detected),
+        _ => None,
+    }
+}
+
+fn epoch_instant(digits: &str, fraction: Option<&str>, unit: EpochUnit) -> Option<DateTime<Utc>> {
+    let epoch = digits.parse::<i64>().ok()?;
+    match fraction {
+        // fractional seconds such as Python's time.time()
+        Some(val) => {
+            let nanos = format!("{:0<9}", val).parse::<u32>().ok()?;
+            // -1.25 is 1.75 seconds before zero, not 0.75
+            let (epoch, nanos) = match digits.starts_with('-') && nanos > 0 {
+                true => (epoch.checked_sub(1)?, 1_000_000_000 - nanos),
+                false => (epoch, nanos),
+            };
+            match Utc.timestamp_opt(epoch, nanos) {
+                LocalResult::Single(instant) => Some(instant),
+                _ => None,
+            }
+        }
+        None => epoch_to_instant(epoch, unit)
+            .ok()
+            .map(|(instant, _)| instant),
+    }
+}
+
+fn find_epochs(line: &str, settings: &Settings) -> Vec<Found> {
+    let bytes = line.as_bytes();
+    let digits_from = |start: usize| {
+        (start..bytes.len())
+            .find(|&i| !bytes[i].is_ascii_digit())
+            .unwrap_or(bytes.len())
+    };
+    let mut found: Vec<Found> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_digit()
+            || (i > 0 && (is_word_byte(bytes[i - 1]) || bytes[i - 1] == b'.'))
+        {
+            i += 1;
+            continue;
+        }
+        // a minus sign counts unless it joins words or numbers, as in 1650627600-1650627609
+        let signed = i > 0 && bytes[i - 1] == b'-' && (i == 1 || !is_word_byte(bytes[i - 2]));
+        let start = match signed {
+            true => i - 1,
+            false => i,
+        };
+        let digits_end = digits_from(i);
+        let mut end = digits_end;
+        let mut fraction = None;
+        if bytes.get(end) == Some(&b'.') && bytes.get(end + 1).is_some_and(u8::is_ascii_digit) {
+            end = digits_from(end + 1);
+            fraction = Some(&line[digits_end + 1..end]);
+        }
+        let standalone = !bytes.get(end).is_some_and(|&byte| is_word_byte(byte));
+        let instant = match (
+            standalone,
+            unit_for_digits(digits_end - i, settings.unit),
+            fraction,
+        ) {
+            (false, _, _) | (_, None, _) => None,
+            (_, Some(EpochUnit::Seconds), Some(val)) if val.len() <= 9 => {
+                epoch_instant(&line[start..digits_end], Some(val), EpochUnit::Seconds)
+            }
+            // a fraction on anything else is more likely a version or a measurement
+            (_, Some(_), Some(_)) => None,
+            (_, Some(unit), None) => epoch_instant(&line[start..digits_end], None, unit),
+        };
+        if let Some(instant) = instant {
+            found.push(Found {
+                start,
+                end,
+                converted: settings.format.format(&instant.with_timezone(&settings.tz)),
+            });
+        }
+        i = end;
+    }
+    found
+}
+
+fn find_dates(line: &str, settings: &Settings) -> Vec<Found> {
+    let words: Vec<(usize, usize)> = line
+        .split_whitespace()
+        .map(|word| {
+            let start = word.as_ptr() as usize - line.as_ptr() as usize;
+            (start, start + word.len())
+        })
+        .collect();
+    let mut found: Vec<Found> = Vec::new();
+    let mut next_word = 0;
+    for first in 0..words.len() {
+        if first < next_word {
+            continue;
+        }
+        // longest run of words first, so "2022-04-22 11:40:09" wins over "2022-04-22"
+        for last in (first..words.len().min(first + MAX_DATE_WORDS)).rev() {
+            let span = &line[words[first].0..words[last].1];
+            let candidate = span.trim_start_matches(OPENING).trim_end_matches(CLOSING);
+            // plain numbers are left to the epoch scan, and keywords like "now" are just words
+            if candidate.is_empty() || candidate.bytes().all(|byte| byte.is_ascii_digit()) {
+                continue;
+            }
+            let conversion = match convert(candidate, settings) {
+                Ok(val) if matches!(val.kind, InputKind::Date | InputKind::DateTime) => val,
+                _ => continue,
+            };
+            let start = words[first].0 + (span.len() - span.trim_start_matches(OPENING).len());
+            found.push(Found {
+                start,
+                end: start + candidate.len(),
+                converted: conversion.output(settings),
+            });
+            next_word = last + 1;
+            break;
+        }
+    }
+    found
+}
+
+pub fn filter_line(line: &str, settings: &Settings, filter: Filter) -> String {
+    let mut found = find_epochs(line, settings);
+    if filter.dates {
+        found.extend(find_dates(line, settings));
+        found.sort_by_key(|val| val.start);
+    }
+
+    let mut filtered = String::with_capacity(line.len());
+    let mut written = 0;
+    for val in found {
+        if val.start < written {
+            continue;
+        }
+        filtered.push_str(&line[written..val.start]);
+        match filter.append {
+            true => {
+                filtered.push_str(&line[val.start..val.end]);
+                filtered.push_str(&format!(" ({})", val.converted));
+            }
+            false => filtered.push_str(&val.converted),
+        }
+        written = val.end;
+    }
+    filtered.push_str(&line[written..]);
+    filtered
+}
+
+pub fn filter_lines<R: Read, W: Write>(
+    reader: &mut BufReader<R>,
+    out: &mut W,
+    settings: &Settings,
+    filter: Filter,
+) -> io::Result<()> {
+    let mut line: Vec<u8> = Vec::new();
+    loop {
+        // flush before waiting on more input so followed logs show up as they're written
+        if reader.buffer().is_empty() {
+            out.flush()?;
+        }
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        // lines that aren't UTF-8 are passed through as they are rather than mangled
+        match std::str::from_utf8(&line) {
+            Ok(val) => out.write_all(filter_line(val, settings, filter).as_bytes())?,
+            Err(_) => out.write_all(&line)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod filter_tests {
+    use super::{filter_line, filter_lines, Filter};
+    use crate::datetime_parsing::EpochUnit;
+    use crate::output_format::OutputFormat;
+    use crate::settings::Settings;
+    use std::io::BufReader;
+
+    fn settings() -> Settings {
+        Settings {
+            format: OutputFormat::parse("%F %T").unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn replace(line: &str) -> String {
+        filter_line(line, &settings(), Filter::default())
+    }
+
+    #[test]
+    fn test_replace_epochs() {
+        assert_eq!(
+            replace("[1650627609] GET /health 200 in 12ms"),
+            "[2022-04-22 11:40:09] GET /health 200 in 12ms"
+        );
+        assert_eq!(
+            replace("ts=1650627609123 next=1650627609123456"),
+            "ts=2022-04-22 11:40:09 next=2022-04-22 11:40:09"
+        );
+        assert_eq!(replace("took 1650627609.25s"), "took 1650627609.25s");
+        assert_eq!(
+            replace("at 1650627609.25 done"),
+            "at 2022-04-22 11:40:09 done"
+        );
+    }
+
+    #[test]
+    fn test_negative_epochs() {
+        assert_eq!(
+            replace("at -1650627609 and (-1650627609.25)"),
+            "at 1917-09-11 12:19:51 and (1917-09-11 12:19:50)"
+        );
+        assert_eq!(
+            replace("range 1650627600-1650627609"),
+            "range 2022-04-22 11:40:00-2022-04-22 11:40:09"
+        );
+        let filter = Filter {
+            append: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            filter_line("-1650627609", &settings(), filter),
+            "-1650627609 (1917-09-11 12:19:51)"
+        );
+    }
+
+    #[test]
+    fn test_leaves_other_numbers() {
+        for line in [
+            "port 8080 pid 31337",
+            "id_1650627609 v1650627609",
+            "3.1650627609",
+            "1.2.1650627609123.4",
+            "16506276091",
+            "1650627609é",
+        ] {
+            assert_eq!(replace(line), line);
+        }
+    }
+
+    #[test]
+    fn test_append() {
+        let filter = Filter {
+            append: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            filter_line(
+                "created 1650627609, updated 1650627610\n",
+                &settings(),
+                filter
+            ),
+            "created 1650627609 (2022-04-22 11:40:09), updated 1650627610 (2022-04-22 11:40:10)\n"
+        );
+    }
+
+    #[test]
+    fn test_unit() {
+        let settings = Settings {
+            unit: EpochUnit::Millis,
+            ..settings()
+        };
+        assert_eq!(
+            filter_line("1650627609 1650627609123", &settings, Filter::default()),
+            "1650627609 2022-04-22 11:40:09"
+        );
+    }
+
+    #[test]
+    fn test_dates() {
+        let filter = Filter {
+            append: true,
+            dates: true,
+        };
+        assert_eq!(
+            filter_line(
+                "(2022-04-22 11:40:09) deployed, rolled back 4/22/2022 at 1650627609.",
+                &settings(),
+                filter
+            ),
+            "(2022-04-22 11:40:09 (1650627609)) deployed, rolled back 4/22/2022 (1650585600) at 1650627609 (2022-04-22 11:40:09)."
+        );
+        // times alone and keywords are too common in prose to rewrite
+        assert_eq!(
+            filter_line("now at 11:40", &settings(), filter),
+            "now at 11:40"
+        );
+    }
+
+    #[test]
+    fn test_filter_lines() {
+        let input: &[u8] = b"a 1650627609\r\n\xff 1650627609\nb";
+        let mut out: Vec<u8> = Vec::new();
+        filter_lines(
+            &mut BufReader::new(input),
+            &mut out,
+            &settings(),
+            Filter::default(),
+        )
+        .unwrap();
+        assert_eq!(out, b"a 2022-04-22 11:40:09\r\n\xff 1650627609\nb");
+    }
+}
@@ -0,0 +1,136 @@
+// Interpret a bare number as every epoch unit and base we know about.
+// There's no way to tell a WebKit timestamp from Unix microseconds by looking at
+// the digits alone, so instead of guessing we list them all, closest to now first.
+
+use chrono::{DateTime, TimeZone, Utc};
+
+const NANOS_PER_SECOND: i128 = 1_000_000_000;
+
+pub struct EpochBase {
+    pub name: &'static str,
+    // size of one unit of the input number
+    pub nanos_per_unit: i128,
+    // where the count starts, in seconds relative to 1970-01-01 UTC
+    pub offset_secs: i64,
+}
+
+pub static EPOCH_BASES: [EpochBase; 10] = [
+    EpochBase {
+        name: "Unix seconds",
+        nanos_per_unit: NANOS_PER_SECOND,
+        offset_secs: 0,
+    },
+    EpochBase {
+        name: "Unix milliseconds",
+        nanos_per_unit: 1_000_000,
+        offset_secs: 0,
+    },
+    EpochBase {
+        name: "Unix microseconds",
+        nanos_per_unit: 1_000,
+        offset_secs: 0,
+    },
+    EpochBase {
+        name: "Unix nanoseconds",
+        nanos_per_unit: 1,
+        offset_secs: 0,
+    },
+    EpochBase {
+        name: "WebKit/Chrome (us since 1601)",
+        nanos_per_unit: 1_000,
+        offset_secs: -11644473600,
+    },
+    EpochBase {
+        name: "Windows FILETIME/LDAP (100ns since 1601)",
+        nanos_per_unit: 100,
+        offset_secs: -11644473600,
+    },
+    EpochBase {
+        name: ".NET ticks (100ns since 0001)",
+        nanos_per_unit: 100,
+        offset_secs: -62135596800,
+    },
+    EpochBase {
+        name: "Apple Cocoa (s since 2001)",
+        nanos_per_unit: NANOS_PER_SECOND,
+        offset_secs: 978307200,
+    },
+    EpochBase {
+        name: "NTP (s since 1900)",
+        nanos_per_unit: NANOS_PER_SECOND,
+        offset_secs: -2208988800,
+    },
+    EpochBase {
+        name: "GPS (s since 1980-01-06)",
+        nanos_per_unit: NANOS_PER_SECOND,
+        offset_secs: 315964800,
+    },
+];
+
+impl EpochBase {
+    pub fn to_datetime(&self, value: i64) -> Option<DateTime<Utc>> {
+        let nanos =
+            value as i128 * self.nanos_per_unit + self.offset_secs as i128 * NANOS_PER_SECOND;
+        let secs = i64::try_from(nanos.div_euclid(NANOS_PER_SECOND)).ok()?;
+        let subsec_nanos = nanos.rem_euclid(NANOS_PER_SECOND) as u32;
+        Utc.timestamp_opt(secs, subsec_nanos).single()
+    }
+}
+
+pub struct Interpretation {
+    pub base: &'static EpochBase,
+    pub datetime: Option<DateTime<Utc>>,
+}
+
+pub fn interpret(value: i64, now: DateTime<Utc>) -> Vec<Interpretation> {
+    // Every base applied to value, most plausible (closest to now) first and
+    // anything outside of chrono's range last.
+    let mut interpretations: Vec<Interpretation> = EPOCH_BASES
+        .iter()
+        .map(|base| Interpretation {
+            base,
+            datetime: base.to_datetime(value),
+        })
+        .collect();
+    interpretations.sort_by_key(|val| match val.datetime {
+        Some(datetime) => (0, (datetime - now).num_seconds().unsigned_abs()),
+        None => (1, 0),
+    });
+    interpretations
+}
+
+#[cfg(test)]
+mod inspect_tests {
+    use super::interpret;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_microseconds_ranked_first() {
+        let now = Utc.timestamp_opt(1650627609, 0).unwrap();
+        let ranked = interpret(1650627609123456, now);
+        assert_eq!(ranked[0].base.name, "Unix microseconds");
+        assert_eq!(
+            ranked[0].datetime,
+            Some(Utc.timestamp_opt(1650627609, 123456000).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_webkit_timestamp() {
+        let now = Utc.timestamp_opt(1650627609, 0).unwrap();
+        let ranked = interpret(13294101209000000, now);
+        assert_eq!(ranked[0].base.name, "WebKit/Chrome (us since 1601)");
+        assert_eq!(
+            ranked[0].datetime,
+            Some(Utc.timestamp_opt(1649627609, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_sorted_last() {
+        let now = Utc.timestamp_opt(1650627609, 0).unwrap();
+        let ranked = interpret(i64::MAX, now);
+        assert_eq!(ranked[0].base.name, "Unix nanoseconds");
+        assert!(ranked.last().unwrap().datetime.is_none());
+    }
+}
@@ -0,0 +1,413 @@
+// Converts timestamp fields inside JSON and NDJSON documents, e.g. `rti json --field .ts < app.log`.
+// Numbers become date/times and date/time strings become epochs, like any other conversion.
+
+use crate::conversion::{convert, InputKind};
+use crate::datetime_parsing::EpochUnit;
+use crate::radix::{format_int, Radix};
+use crate::settings::Settings;
+use chrono::{DateTime, TimeZone, Utc};
+use serde_json::{Map, Number, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+const ADDED_SUFFIX: &str = "_converted";
+
+// A document still incomplete after this many lines is taken not to be JSON after all
+const MAX_DOCUMENT_LINES: usize = 10_000;
+
+// Floats at least this big are milliseconds, matching how whole numbers are read
+const PROBABLY_MILLIS_FLOAT: f64 = 1e12;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    // every element of an array
+    Each,
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPath {
+    source: String,
+    steps: Vec<Step>,
+}
+
+impl FieldPath {
+    pub fn parse(input: &str) -> Result<FieldPath, String> {
+        // jq style: .meta.created, .events[].ts or .events[0].ts. The leading dot is optional.
+        let invalid = || {
+            format!(
+                "Invalid field: {}. Use a path like .ts, .meta.created or .events[].ts.",
+                input
+            )
+        };
+        let mut steps: Vec<Step> = Vec::new();
+        let mut rest = input.strip_prefix('.').unwrap_or(input);
+        while !rest.is_empty() {
+            if let Some(inner) = rest.strip_prefix('[') {
+                let (index, after) = inner.split_once(']').ok_or_else(invalid)?;
+                steps.push(match index {
+                    "" => Step::Each,
+                    _ => Step::Index(index.parse().map_err(|_| invalid())?),
+                });
+                rest = after.strip_prefix('.').unwrap_or(after);
+                continue;
+            }
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            steps.push(Step::Key(rest[..end].to_string()));
+            rest = rest[end..].strip_prefix('.').unwrap_or(&rest[end..]);
+        }
+        match steps.last() {
+            Some(Step::Key(_)) => Ok(FieldPath {
+                source: input.to_string(),
+                steps,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+fn visit(value: &mut Value, steps: &[Step], leaf: &mut dyn FnMut(&mut Map<String, Value>, &str)) {
+    // Missing keys and type mismatches just mean there's nothing to convert here.
+    // A key applied to an array applies to each element, so .events.ts works like .events[].ts
+    match (value, steps) {
+        (Value::Array(items), [Step::Key(_), ..]) => {
+            for item in items {
+                visit(item, steps, leaf);
+            }
+        }
+        (Value::Object(map), [Step::Key(key)]) => leaf(map, key),
+        (Value::Object(map), [Step::Key(key), rest @ ..]) => {
+            if let Some(child) = map.get_mut(key) {
+                visit(child, rest, leaf);
+            }
+        }
+        (Value::Array(items), [Step::Each, rest @ ..]) => {
+            for item in items {
+                visit(item, rest, leaf);
+            }
+        }
+        (Value::Array(items), [Step::Index(index), rest @ ..]) => {
+            if let Some(item) = items.get_mut(*index) {
+                visit(item, rest, leaf);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn float_instant(value: f64, unit: EpochUnit) -> Option<DateTime<Utc>> {
+    // fractional epochs such as Python's time.time() or JavaScript's performance timings
+    let unit = match unit {
+        EpochUnit::Auto if value.abs() >= PROBABLY_MILLIS_FLOAT => EpochUnit::Millis,
+        EpochUnit::Auto => EpochUnit::Seconds,
+        _ => unit,
+    };
+    let seconds = value / unit.per_second() as f64;
+    if !seconds.is_finite() || seconds.abs() >= i64::MAX as f64 {
+        return None;
+    }
+    let whole = seconds.floor();
+    let nanos = ((seconds - whole) * 1e9).round().min(999_999_999.0) as u32;
+    Utc.timestamp_opt(whole as i64, nanos).single()
+}
+
+fn convert_value(value: &Value, settings: &Settings) -> Option<Result<Value, String>> {
+    let text = match value {
+        Value::Number(number) if number.is_f64() => {
+            let converted = number
+                .as_f64()
+                .and_then(|val| float_instant(val, settings.unit))
+                .map(|instant| settings.format.format(&instant.with_timezone(&settings.tz)))
+                .map(Value::String)
+                .ok_or(format!("Epoch {} is out of range.", number));
+            return Some(converted);
+        }
+        Value::Number(number) => number.to_string(),
+        Value::String(val) if !val.trim().is_empty() => val.trim().to_string(),
+        Value::Array(items) => {
+            // a list of timestamps converts element by element
+            let converted = items
+                .iter()
+                .map(|item| convert_value(item, settings).unwrap_or_else(|| Ok(item.clone())))
+                .collect::<Result<Vec<Value>, String>>()
+                .map(Value::Array);
+            return Some(converted);
+        }
+        _ => return None,
+    };
+    let conversion = match convert(&text, settings) {
+        Ok(val) => val,
+        Err(e) => return Some(Err(format!("{}: {}", text, e))),
+    };
+    let converted = match (conversion.kind, conversion.epoch_in(settings.unit)) {
+        (InputKind::Epoch | InputKind::ObjectId, _) => Value::String(conversion.output(settings)),
+        // epochs stay numbers so they're still usable as numbers downstream
+        (_, Some(epoch)) if settings.radix == Radix::Decimal => Value::Number(Number::from(epoch)),
+        (_, Some(epoch)) => Value::String(format_int(epoch, settings.radix)),
+        (_, None) => return Some(Err(format!("{}: out of range", text))),
+    };
+    Some(Ok(converted))
+}
+
+fn convert_field(map: &mut Map<String, Value>, key: &str, add: bool, converted: Value) {
+    if !add {
+        map.insert(key.to_string(), converted);
+        return;
+    }
+    // rebuild so the new field sits right after the original
+    let added_key = format!("{}{}", key, ADDED_SUFFIX);
+    let mut converted = Some(converted);
+    for (name, value) in std::mem::take(map) {
+        let is_key = name == key;
+        if name != added_key {
+            map.insert(name, value);
+        }
+        if is_key {
+            if let Some(val) = converted.take() {
+                map.insert(added_key.clone(), val);
+            }
+        }
+    }
+}
+
+pub fn convert_document(
+    document: &mut Value,
+    fields: &[FieldPath],
+    add: bool,
+    settings: &Settings,
+) -> (usize, Vec<String>) {
+    // returns how many values were converted and a description of each one that couldn't be
+    let mut count = 0;
+    let mut errors: Vec<String> = Vec::new();
+    for field in fields {
+        visit(document, &field.steps, &mut |map, key| {
+            let converted = match map.get(key).and_then(|val| convert_value(val, settings)) {
+                Some(Ok(val)) => val,
+                Some(Err(e)) => {
+                    errors.push(format!("{} ({})", e, field.source));
+                    return;
+                }
+                None => return,
+            };
+            convert_field(map, key, add, converted);
+            count += 1;
+        });
+    }
+    (count, errors)
+}
+
+fn starts_document(line: &str) -> bool {
+    // pretty printers indent everything inside a document, so only a new one starts in the first column
+    line.starts_with(['{', '['])
+}
+
+pub fn convert_documents<R: Read, W: Write>(
+    reader: &mut BufReader<R>,
+    out: &mut W,
+    fields: &[FieldPath],
+    add: bool,
+    settings: &Settings,
+) -> io::Result<usize> {
+    // Returns how many values couldn't be converted.
+    // One document per line for NDJSON, but a document may also span lines like pretty printed JSON.
+    // Anything that isn't JSON, such as plain log lines, passes through untouched.
+    let mut pending = String::new();
+    let mut lines = 0;
+    let mut number = 0;
+    let mut failures = 0;
+    loop {
+        // flush before waiting on more input, like the other line by line modes
+        if reader.buffer().is_empty() {
+            out.flush()?;
+        }
+        let line_start = pending.len();
+        let read = reader.read_line(&mut pending)?;
+        if read == 0 {
+            out.write_all(pending.as_bytes())?;
+            return Ok(failures);
+        }
+        lines += 1;
+        // a stray "{" or a truncated document shouldn't hold back everything after it
+        if lines > 1 && starts_document(&pending[line_start..]) {
+            out.write_all(&pending.as_bytes()[..line_start])?;
+            pending.drain(..line_start);
+            lines = 1;
+        }
+        if pending.trim().is_empty() {
+            out.write_all(pending.as_bytes())?;
+        } else {
+            match serde_json::from_str::<Value>(&pending) {
+                Ok(mut document) => {
+                    number += 1;
+                    let (count, errors) = convert_document(&mut document, fields, add, settings);
+                    for error in &errors {
+                        // stdout is the JSON itself, so problems go to stderr
+                        eprintln!("Unable to parse value: {} in document {}", error, number);
+                    }
+                    failures += errors.len();
+                    // reserializing would still reformat the rest, so leave documents with nothing converted alone
+                    if count == 0 {
+                        out.write_all(pending.as_bytes())?;
+                    } else {
+                        let serialized = match lines {
+                            1 => serde_json::to_string(&document),
+                            _ => serde_json::to_string_pretty(&document),
+                        };
+                        writeln!(out, "{}", serialized.map_err(io::Error::other)?)?;
+                    }
+                    if settings.fail_fast && failures > 0 {
+                        out.flush()?;
+                        return Ok(failures);
+                    }
+                }
+                Err(e) if e.is_eof() && lines < MAX_DOCUMENT_LINES => continue,
+                Err(_) => out.write_all(pending.as_bytes())?,
+            }
+        }
+        pending.clear();
+        lines = 0;
+    }
+}
+
+#[cfg(test)]
+mod json_fields_tests {
+    use super::{convert_document, convert_documents, FieldPath, MAX_DOCUMENT_LINES};
+    use crate::settings::Settings;
+    use serde_json::{json, Value};
+    use std::io::BufReader;
+
+    fn paths(fields: &[&str]) -> Vec<FieldPath> {
+        fields
+            .iter()
+            .map(|field| FieldPath::parse(field).unwrap())
+            .collect()
+    }
+
+    fn converted(mut document: Value, fields: &[&str], add: bool) -> (Value, Vec<String>) {
+        let (_, errors) =
+            convert_document(&mut document, &paths(fields), add, &Settings::default());
+        (document, errors)
+    }
+
+    #[test]
+    fn test_paths() {
+        assert!(FieldPath::parse(".ts").is_ok());
+        assert!(FieldPath::parse("meta.created").is_ok());
+        assert!(FieldPath::parse(".events[].ts").is_ok());
+        assert!(FieldPath::parse(".events[2].ts").is_ok());
+        assert!(FieldPath::parse(".").is_err());
+        assert!(FieldPath::parse(".events[]").is_err());
+        assert!(FieldPath::parse(".events[x].ts").is_err());
+        assert!(FieldPath::parse(".a..b").is_err());
+    }
+
+    #[test]
+    fn test_replace() {
+        let (document, errors) = converted(
+            json!({"ts": 1650627609, "meta": {"created": "2022-04-22 11:40:09", "n": 1}}),
+            &[".ts", ".meta.created", ".missing.path"],
+            false,
+        );
+        assert_eq!(
+            document,
+            json!({"ts": "04-22-2022 11:40:09", "meta": {"created": 1650627609, "n": 1}})
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_add_and_arrays() {
+        let (document, errors) = converted(
+            json!({"events": [{"at": 1650627609.5, "id": 1}, {"at": "bogus"}, {"id": 3}], "times": [1650627609, null]}),
+            &[".events[].at", ".times"],
+            true,
+        );
+        assert_eq!(
+            document,
+            json!({
+                "events": [
+                    {"at": 1650627609.5, "at_converted": "04-22-2022 11:40:09", "id": 1},
+                    {"at": "bogus"},
+                    {"id": 3}
+                ],
+                "times": [1650627609, null],
+                "times_converted": ["04-22-2022 11:40:09", null]
+            })
+        );
+        assert_eq!(errors, vec!["bogus: Invalid Pattern (.events[].at)"]);
+        // the new field goes right after the original
+        let keys: Vec<&String> = document["events"][0].as_object().unwrap().keys().collect();
+        assert_eq!(keys, vec!["at", "at_converted", "id"]);
+    }
+
+    #[test]
+    fn test_documents() {
+        let input = "{\"ts\":1650627609}\nnot json\n\n{\n  \"ts\": 1650627610\n}\n";
+        let mut out: Vec<u8> = Vec::new();
+        convert_documents(
+            &mut BufReader::new(input.as_bytes()),
+            &mut out,
+            &paths(&[".ts"]),
+            false,
+            &Settings::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"ts\":\"04-22-2022 11:40:09\"}\nnot json\n\n{\n  \"ts\": \"04-22-2022 11:40:10\"\n}\n"
+        );
+    }
+
+    fn convert_ts(input: &str) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        convert_documents(
+            &mut BufReader::new(input.as_bytes()),
+            &mut out,
+            &paths(&[".ts"]),
+            false,
+            &Settings::default(),
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_incomplete_documents() {
+        // the next document starting ends an incomplete one
+        assert_eq!(
+            convert_ts("{\n{\"ts\":1650627609}\n{\"a\":\n{\"ts\":1650627610}\n"),
+            "{\n{\"ts\":\"04-22-2022 11:40:09\"}\n{\"a\":\n{\"ts\":\"04-22-2022 11:40:10\"}\n"
+        );
+        // nested documents in pretty output are indented, so they don't count
+        assert_eq!(
+            convert_ts("[\n  {\n    \"ts\": 1650627609\n  }\n]\n"),
+            "[\n  {\n    \"ts\": \"04-22-2022 11:40:09\"\n  }\n]\n"
+        );
+        // and anything else only waits so long
+        let blank = "\n".repeat(MAX_DOCUMENT_LINES);
+        assert_eq!(
+            convert_ts(&format!("{{{}plain\n", blank)),
+            format!("{{{}plain\n", blank)
+        );
+    }
+
+    #[test]
+    fn test_untouched_values() {
+        // numbers keep their exact text, whether or not anything else in the document was converted
+        assert_eq!(
+            convert_ts("{\"id\":123456789012345678901234567890,\"ts\":1650627609,\"p\":0.30000000000000004441}\n"),
+            "{\"id\":123456789012345678901234567890,\"ts\":\"04-22-2022 11:40:09\",\"p\":0.30000000000000004441}\n"
+        );
+        assert_eq!(
+            convert_ts("{ \"id\": 123456789012345678901234567890 }\n1e3\n1.50\n"),
+            "{ \"id\": 123456789012345678901234567890 }\n1e3\n1.50\n"
+        );
+        assert_eq!(
+            convert_ts("{\"ts\":1.6506276095e9}\n"),
+            "{\"ts\":\"04-22-2022 11:40:09\"}\n"
+        );
+    }
+}
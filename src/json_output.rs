@@ -0,0 +1,189 @@
+// Machine readable output: one object per argument with everything we know
+// about the conversion, and an error field instead of "Unable to parse value".
+
+use crate::conversion::{convert, Conversion};
+use crate::failure::Failure;
+use crate::settings::Settings;
+use chrono::SecondsFormat;
+use serde::Serialize;
+use std::io::{self, Write};
+
+#[derive(Serialize)]
+struct ZoneRecord {
+    zone: String,
+    iso: String,
+    offset: String,
+    dst: bool,
+}
+
+#[derive(Serialize, Default)]
+struct JsonRecord {
+    input: String,
+    kind: Option<&'static str>,
+    pattern: Option<String>,
+    epoch: Option<i64>,
+    epoch_ms: Option<i64>,
+    epoch_us: Option<i64>,
+    // null once the instant no longer fits in 64 bits of nanoseconds (after 2262)
+    epoch_ns: Option<i64>,
+    iso: Option<String>,
+    formatted: Option<String>,
+    timezone: String,
+    offset: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    zones: Vec<ZoneRecord>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    notes: Vec<String>,
+    error: Option<String>,
+}
+
+fn record_for(conversion: Conversion, settings: &Settings) -> JsonRecord {
+    let instant = conversion.instant;
+    let local = instant.with_timezone(&settings.tz);
+    let zones = settings
+        .zones
+        .iter()
+        .map(|zone| {
+            let zoned = instant.with_timezone(zone);
+            ZoneRecord {
+                zone: zone.to_string(),
+                iso: zoned.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                offset: zoned.format("%:z").to_string(),
+                dst: zoned.offset().is_dst(),
+            }
+        })
+        .collect();
+
+    JsonRecord {
+        epoch: Some(conversion.epoch()),
+        epoch_ms: Some(conversion.epoch_ms()),
+        epoch_us: Some(conversion.epoch_us()),
+        epoch_ns: conversion.epoch_ns(),
+        kind: Some(conversion.kind.name()),
+        pattern: conversion.pattern,
+        iso: Some(local.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+        formatted: Some(settings.format.format(&local)),
+        timezone: settings.tz.to_string(),
+        offset: Some(local.format("%:z").to_string()),
+        zones,
+        notes: conversion.notes,
+        input: conversion.input,
+        error: None,
+    }
+}
+
+fn json_record(arg: &str, settings: &Settings) -> JsonRecord {
+    match convert(arg, settings) {
+        Ok(conversion) => record_for(conversion, settings),
+        Err(e) => JsonRecord {
+            input: arg.to_string(),
+            timezone: settings.tz.to_string(),
+            error: Some(e),
+            ..Default::default()
+        },
+    }
+}
+
+pub fn write_ndjson<W: Write>(out: &mut W, arg: &str, settings: &Settings) -> io::Result<bool> {
+    // One record per line as each value arrives, so streams like `tail -f | rti --ndjson` work.
+    // Returns true when the value converted.
+    let record = json_record(arg, settings);
+    serde_json::to_writer(&mut *out, &record).map_err(io::Error::other)?;
+    writeln!(out)?;
+    Ok(record.error.is_none())
+}
+
+pub fn print_json(args: &[String], settings: &Settings) -> Result<usize, Failure> {
+    // A single array needs every value first, unlike NDJSON
+    // returns how many records carry an error, stopping at the first one with --fail-fast
+    let mut records: Vec<JsonRecord> = Vec::new();
+    for arg in args {
+        records.push(json_record(arg, settings));
+        if settings.fail_fast && records.iter().any(|record| record.error.is_some()) {
+            break;
+        }
+    }
+    let failures = records
+        .iter()
+        .filter(|record| record.error.is_some())
+        .count();
+    match serde_json::to_string_pretty(&records) {
+        Ok(val) => {
+            println!("{}", val);
+            Ok(failures)
+        }
+        Err(e) => Err(Failure::Io(format!("Error writing JSON: {}", e))),
+    }
+}
+
+#[cfg(test)]
+mod json_output_tests {
+    use super::json_record;
+    use crate::settings::Settings;
+    use crate::timezone::Zone;
+    use chrono_tz::America::New_York;
+    use serde_json::{json, Value};
+
+    fn settings() -> Settings {
+        Settings {
+            zones: vec![Zone::Named(New_York)],
+            ..Default::default()
+        }
+    }
+
+    fn to_value(arg: &str) -> Value {
+        serde_json::to_value(json_record(arg, &settings())).unwrap()
+    }
+
+    #[test]
+    fn test_epoch_record() {
+        assert_eq!(
+            to_value("1650627609123"),
+            json!({
+                "input": "1650627609123",
+                "kind": "epoch",
+                "pattern": null,
+                "epoch": 1650627609,
+                "epoch_ms": 1650627609123i64,
+                "epoch_us": 1650627609123000i64,
+                "epoch_ns": 1650627609123000000i64,
+                "iso": "2022-04-22T11:40:09.123Z",
+                "formatted": "04-22-2022 11:40:09",
+                "timezone": "UTC",
+                "offset": "+00:00",
+                "zones": [{
+                    "zone": "America/New_York",
+                    "iso": "2022-04-22T07:40:09.123-04:00",
+                    "offset": "-04:00",
+                    "dst": true
+                }],
+                "notes": ["Parsing epoch time as milliseconds."],
+                "error": null
+            })
+        );
+    }
+
+    #[test]
+    fn test_datetime_record() {
+        let value = to_value("2022-04-22 13:40:09");
+        assert_eq!(value["kind"], "datetime");
+        assert_eq!(value["pattern"], "%F %H:%M:%S");
+        assert_eq!(value["epoch"], 1650634809);
+        assert_eq!(value["error"], Value::Null);
+    }
+
+    #[test]
+    fn test_keyword_record() {
+        let value = to_value("now");
+        assert_eq!(value["kind"], "keyword");
+        assert_eq!(value["pattern"], "now");
+    }
+
+    #[test]
+    fn test_error_record() {
+        let value = to_value("not a date");
+        assert_eq!(value["error"], "Invalid Pattern");
+        assert_eq!(value["epoch"], Value::Null);
+        assert_eq!(value["timezone"], "UTC");
+    }
+}
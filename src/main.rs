@@ -1,70 +1,599 @@
 extern crate chrono;
-use chrono_tz::Tz;
 mod config;
 use config::{
-    add_custom_token, clear_tz_config, get_custom_tokens, get_timezone, remove_custom_token,
-    set_tz_config, view_tokens,
+    add_custom_token, add_display_zone, add_snowflake_layout, clear_format_config,
+    clear_locale_config, clear_template_config, clear_tz_config, get_snowflake_layouts,
+    remove_custom_token, remove_display_zone, remove_snowflake_layout, set_format_config,
+    set_locale_config, set_template_config, set_tz_config, view_display_zones,
+    view_snowflake_layouts, view_tokens,
 };
-use std::env;
 
 mod datetime_parsing;
-use datetime_parsing::{epoch_to_datetime, parse_arg};
+use datetime_parsing::{epoch_to_datetime, format_datetime, parse_arg};
+
+mod completion;
+use completion::{complete_from_env, write_script};
+
+mod conversion;
+use conversion::{convert, Conversion};
+
+mod csv_columns;
+use csv_columns::{convert_records, read_record, CsvColumns};
+
+mod failure;
+use failure::{Failure, OkOrFailure};
+
+mod explain;
+use explain::write_breakdown;
+
+mod filter;
+use filter::{filter_lines, Filter};
+
+mod inspect;
+
+mod json_fields;
+use json_fields::convert_documents;
+
+mod manual;
+use manual::write_man_page;
+
+mod json_output;
+use json_output::{print_json, write_ndjson};
+
+mod output_format;
+
+mod object_id;
+use object_id::epoch_to_object_id;
+
+mod snowflake;
+use snowflake::SnowflakeLayout;
+
+mod radix;
+use radix::parse_int;
+
+mod relative;
+use relative::relative;
+
+mod repl;
+
+mod settings;
+use settings::{OutputMode, Settings};
+
+mod table_output;
+use table_output::print_table;
+
+mod template;
+
+mod timezone;
+
+mod zones;
+use zones::print_zone_table;
 
 mod cli;
-use cli::{help, parse_input, Action, ParsedInput};
+use clap::{CommandFactory, Parser};
+use cli::{Action, Cli};
 
-pub type OkOrStringError = Result<Option<String>, String>;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
 
-fn fmt_and_print(arg: String, tz: &Tz, custom_tokens: &Vec<String>) {
-    let maybe_int_parse = arg.parse::<i64>();
+// Stands for every line on stdin. Giving no values at all does the same when stdin is piped.
+const STDIN_VALUE: &str = "-";
 
-    let parsed_value = match maybe_int_parse {
-        Ok(val) => epoch_to_datetime(val, tz),
-        Err(_) => parse_arg(&arg, tz, custom_tokens),
-    };
+fn report<W: Write>(out: &mut W, message: &str) -> io::Result<()> {
+    // diagnostics go to stderr, after everything before them has reached stdout
+    out.flush()?;
+    eprintln!("{}", message);
+    Ok(())
+}
 
-    match parsed_value {
-        Ok(val) => println!("{} => {}", arg, val),
-        Err(_) => println!("Unable to parse value: {}", arg),
+fn write_conversion<W: Write>(
+    out: &mut W,
+    conversion: &Conversion,
+    settings: &Settings,
+) -> io::Result<()> {
+    let arg = &conversion.input;
+    for note in &conversion.notes {
+        report(out, note)?;
+    }
+    match (&settings.template, settings.relative) {
+        (Some(template), _) => writeln!(out, "{}", template.render(conversion, settings))?,
+        (None, Some(mode)) => {
+            let reference = settings.reference.unwrap_or_else(chrono::Utc::now);
+            writeln!(
+                out,
+                "{} => {} ({})",
+                arg,
+                conversion.output(settings),
+                relative(&conversion.instant, &reference, mode)
+            )?
+        }
+        (None, None) => writeln!(out, "{} => {}", arg, conversion.output(settings))?,
+    }
+    if settings.units {
+        writeln!(out, "    {}", conversion.units(settings))?;
     }
+    if settings.verbose {
+        write_breakdown(
+            out,
+            &conversion.instant.with_timezone(&settings.tz),
+            settings.locale,
+        )?;
+    }
+    Ok(())
 }
 
-fn execute_action(input: ParsedInput) -> OkOrStringError {
-    match input.action {
-        Some(Action::Help) => help(),
-        Some(Action::SetTz) => set_tz_config(input.second_arg),
-        Some(Action::ClearTz) => clear_tz_config(),
-        Some(Action::AddToken) => add_custom_token(input.second_arg),
-        Some(Action::RemoveToken) => remove_custom_token(input.second_arg),
-        Some(Action::ViewTokens) => view_tokens(),
-        _ => {
-            let tz: Tz = get_timezone();
-            let custom_tokens: Vec<String> = get_custom_tokens();
-            for elem in input.date_args {
-                fmt_and_print(elem.to_string(), &tz, &custom_tokens);
+fn fmt_and_print<W: Write>(out: &mut W, arg: &str, settings: &Settings) -> io::Result<bool> {
+    // true when the value converted
+    if settings.output == OutputMode::Ndjson {
+        return write_ndjson(out, arg, settings);
+    }
+    match convert(arg, settings) {
+        Ok(conversion) => write_conversion(out, &conversion, settings)?,
+        Err(e) => {
+            report(out, &format!("Unable to parse value: {} ({})", arg, e))?;
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn print_footer<W: Write>(out: &mut W, settings: &Settings) -> io::Result<()> {
+    // templates carry {tz} themselves when they want it, and NDJSON has it in every record
+    match (&settings.template, settings.output) {
+        (Some(_), _) | (_, OutputMode::Ndjson) => Ok(()),
+        (None, _) => writeln!(out, "Timezone: {}", settings.tz),
+    }
+}
+
+fn convert_lines<R: Read, W: Write>(
+    reader: &mut BufReader<R>,
+    out: &mut W,
+    settings: &Settings,
+) -> io::Result<usize> {
+    // returns how many lines couldn't be converted
+    let mut failures = 0;
+    let mut line = String::new();
+    loop {
+        // flush before waiting on more input so `tail -f | rti` shows each line as it arrives
+        if reader.buffer().is_empty() {
+            out.flush()?;
+        }
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(failures);
+        }
+        let value = line.trim();
+        if !value.is_empty() && !fmt_and_print(out, value, settings)? {
+            failures += 1;
+            if settings.fail_fast {
+                return Ok(failures);
             }
-            println!("Timezone: {}", tz);
-            Ok(None)
         }
     }
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let input = match parse_input(args) {
-        Ok(val) => val,
-        Err(e) => {
-            println!("Error: {}", e);
-            return;
+fn print_values(values: &[String], settings: &Settings) -> io::Result<usize> {
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut failures = 0;
+    for value in values {
+        failures += match value.as_str() {
+            STDIN_VALUE => {
+                convert_lines(&mut BufReader::new(io::stdin().lock()), &mut out, settings)?
+            }
+            _ => usize::from(!fmt_and_print(&mut out, value, settings)?),
+        };
+        if settings.fail_fast && failures > 0 {
+            break;
         }
+    }
+    print_footer(&mut out, settings)?;
+    out.flush()?;
+    Ok(failures)
+}
+
+fn read_values(values: Vec<String>) -> Result<Vec<String>, Failure> {
+    // JSON arrays, tables and zone columns need every value up front, so stdin is read to the end
+    let mut expanded: Vec<String> = Vec::new();
+    for value in values {
+        match value.as_str() {
+            STDIN_VALUE => {
+                for line in io::stdin().lock().lines() {
+                    let line =
+                        line.map_err(|e| Failure::Io(format!("Unable to read values: {}", e)))?;
+                    if !line.trim().is_empty() {
+                        expanded.push(line.trim().to_string());
+                    }
+                }
+            }
+            _ => expanded.push(value),
+        }
+    }
+    Ok(expanded)
+}
+
+fn values_result(failures: usize) -> OkOrFailure {
+    // each failure has already been reported, this only decides the exit code
+    match failures {
+        0 => Ok(None),
+        _ => Err(Failure::Values),
+    }
+}
+
+fn write_result(result: io::Result<usize>) -> OkOrFailure {
+    match result {
+        Ok(failures) => values_result(failures),
+        // the reader went away, e.g. `rti - | head`
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(None),
+        Err(e) => Err(Failure::Io(format!(
+            "Unable to read or write values: {}",
+            e
+        ))),
+    }
+}
+
+fn arg_to_epoch(arg: &str, settings: &Settings) -> Result<i64, String> {
+    // Accept either an epoch or anything parse_arg understands
+    match parse_int(arg, settings.endian) {
+        Some(val) => Ok(val),
+        None => parse_arg(arg, &settings.tz, &settings.custom_tokens)
+            .and_then(|val| val.parse::<i64>().map_err(|_| val)),
+    }
+}
+
+fn get_snowflake_layout(name: &str) -> Result<SnowflakeLayout, Failure> {
+    match snowflake::find_layout(name, &get_snowflake_layouts()?) {
+        // a hand edited config never went through add-snowflake
+        Some(layout) => match layout.validate() {
+            Ok(_) => Ok(layout),
+            Err(e) => Err(Failure::Config(format!("{} (from config)", e))),
+        },
+        None => Err(Failure::Usage(format!(
+            "Unknown snowflake layout: {}. See view-snowflakes for available layouts.",
+            name
+        ))),
+    }
+}
+
+fn decode_snowflakes(layout: &str, ids: &[String], settings: &Settings) -> OkOrFailure {
+    let layout = get_snowflake_layout(layout)?;
+    let mut failures = 0;
+    for arg in ids {
+        let decoded = match arg.parse::<u64>() {
+            Ok(id) => snowflake::decode(id, &layout),
+            Err(_) => Err("Snowflake IDs must be positive integers.".to_string()),
+        };
+        let datetime = decoded.and_then(|val| {
+            let epoch = val.timestamp_ms.div_euclid(1000);
+            epoch_to_datetime(epoch, &settings.tz, &settings.format).map(|dt| (dt, val))
+        });
+        match datetime {
+            Ok((dt, val)) => println!(
+                "{} => {} (worker: {}, sequence: {})",
+                arg, dt, val.worker, val.sequence
+            ),
+            Err(e) => {
+                eprintln!("Unable to parse value: {} ({})", arg, e);
+                failures += 1;
+                if settings.fail_fast {
+                    break;
+                }
+            }
+        }
+    }
+    println!("Timezone: {}", settings.tz);
+    values_result(failures)
+}
+
+fn lowest_snowflakes(layout: &str, args: &[String], settings: &Settings) -> OkOrFailure {
+    let layout = get_snowflake_layout(layout)?;
+    let mut failures = 0;
+    for arg in args {
+        let lowest = arg_to_epoch(arg, settings).and_then(|val| match val.checked_mul(1000) {
+            Some(epoch_ms) => snowflake::lowest_for(epoch_ms, &layout),
+            None => Err("Epoch is out of range.".to_string()),
+        });
+        match lowest {
+            Ok(id) => println!("{} => {}", arg, id),
+            Err(e) => {
+                eprintln!("Unable to parse value: {} ({})", arg, e);
+                failures += 1;
+                if settings.fail_fast {
+                    break;
+                }
+            }
+        }
+    }
+    println!("Timezone: {}", settings.tz);
+    values_result(failures)
+}
+
+fn lowest_object_ids(args: &[String], settings: &Settings) -> OkOrFailure {
+    let mut failures = 0;
+    for arg in args {
+        match arg_to_epoch(arg, settings).and_then(epoch_to_object_id) {
+            Ok(id) => println!("{} => {}", arg, id),
+            Err(e) => {
+                eprintln!("Unable to parse value: {} ({})", arg, e);
+                failures += 1;
+                if settings.fail_fast {
+                    break;
+                }
+            }
+        }
+    }
+    println!("Timezone: {}", settings.tz);
+    values_result(failures)
+}
+
+fn inspect_values(args: &[String], settings: &Settings) -> OkOrFailure {
+    let now = chrono::Utc::now();
+    let mut failures = 0;
+    for arg in args {
+        let value = match parse_int(arg, settings.endian) {
+            Some(val) => val,
+            None => {
+                eprintln!("Unable to parse value: {}", arg);
+                failures += 1;
+                match settings.fail_fast {
+                    true => break,
+                    false => continue,
+                }
+            }
+        };
+        println!("{} (most plausible first):", arg);
+        for (rank, interpretation) in inspect::interpret(value, now).iter().enumerate() {
+            let shown = match interpretation.datetime {
+                Some(datetime) => format_datetime(&datetime, &settings.tz, &settings.format),
+                None => "out of range".to_string(),
+            };
+            println!("  {}. {} => {}", rank + 1, interpretation.base.name, shown);
+        }
+    }
+    println!("Timezone: {}", settings.tz);
+    values_result(failures)
+}
+
+fn open_input(file: Option<PathBuf>) -> Result<BufReader<Box<dyn Read>>, Failure> {
+    let input: Box<dyn Read> = match file {
+        Some(path) if path.as_os_str() != STDIN_VALUE => match File::open(&path) {
+            Ok(val) => Box::new(val),
+            Err(e) => {
+                return Err(Failure::Io(format!(
+                    "Unable to open {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        },
+        _ => Box::new(io::stdin()),
     };
+    Ok(BufReader::new(input))
+}
+
+fn convert_csv(
+    file: Option<PathBuf>,
+    selectors: &[String],
+    add: bool,
+    no_header: bool,
+    settings: &Settings,
+) -> OkOrFailure {
+    let mut reader = open_input(file)?;
+    let header = match no_header {
+        true => None,
+        false => match read_record(&mut reader) {
+            Ok(Some(val)) => Some(val),
+            // nothing to convert
+            Ok(None) => return Ok(None),
+            Err(e) => return write_result(Err(e)),
+        },
+    };
+    let columns = CsvColumns::resolve(selectors, header.as_deref(), add)?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    write_result(convert_records(
+        &mut reader,
+        &mut out,
+        &columns,
+        header.as_deref(),
+        settings,
+    ))
+}
 
-    match execute_action(input) {
+fn execute_action(cli: Cli) -> OkOrFailure {
+    let options = &cli.options;
+    let mut values = cli.values;
+    if values.is_empty() && cli.action.is_none() {
+        match io::stdin().is_terminal() {
+            true => {
+                return Cli::command()
+                    .print_help()
+                    .map(|_| None)
+                    .map_err(|e| Failure::Io(e.to_string()))
+            }
+            false => values.push(STDIN_VALUE.to_string()),
+        }
+    }
+    match cli.action {
+        Some(Action::SetTz { timezone }) => set_tz_config(Some(timezone)),
+        Some(Action::ClearTz) => clear_tz_config(),
+        Some(Action::AddToken { token }) => add_custom_token(Some(token)),
+        Some(Action::RemoveToken { token }) => remove_custom_token(Some(token)),
+        Some(Action::ViewTokens) => view_tokens(),
+        Some(Action::SetFormat { format }) => set_format_config(Some(format)),
+        Some(Action::ClearFormat) => clear_format_config(),
+        Some(Action::SetTemplate { template }) => set_template_config(Some(template)),
+        Some(Action::ClearTemplate) => clear_template_config(),
+        Some(Action::SetLocale { locale }) => set_locale_config(Some(locale)),
+        Some(Action::ClearLocale) => clear_locale_config(),
+        Some(Action::AddZone { timezone }) => add_display_zone(Some(timezone)),
+        Some(Action::RemoveZone { timezone }) => remove_display_zone(Some(timezone)),
+        Some(Action::ViewZones) => view_display_zones(),
+        Some(Action::AddSnowflake {
+            name,
+            epoch_ms,
+            worker_bits,
+            sequence_bits,
+        }) => add_snowflake_layout(&[name, epoch_ms, worker_bits, sequence_bits]),
+        Some(Action::RemoveSnowflake { name }) => remove_snowflake_layout(Some(name)),
+        Some(Action::ViewSnowflakes) => view_snowflake_layouts(),
+        Some(Action::Snowflake { layout, ids }) => {
+            decode_snowflakes(&layout, &ids, &Settings::load(options)?)
+        }
+        Some(Action::SnowflakeAt { layout, values }) => {
+            lowest_snowflakes(&layout, &values, &Settings::load(options)?)
+        }
+        Some(Action::ObjectIdAt { values }) => {
+            lowest_object_ids(&values, &Settings::load(options)?)
+        }
+        Some(Action::Inspect { values }) => inspect_values(&values, &Settings::load(options)?),
+        Some(Action::Filter { append, dates }) => {
+            let settings = Settings::load(options)?;
+            let mut out = BufWriter::new(io::stdout().lock());
+            let filtered = filter_lines(
+                &mut BufReader::new(io::stdin().lock()),
+                &mut out,
+                &settings,
+                Filter { append, dates },
+            );
+            // free text isn't expected to be all timestamps, so there are no failures to count
+            write_result(filtered.map(|_| 0))
+        }
+        Some(Action::Csv {
+            column,
+            add,
+            no_header,
+            file,
+        }) => convert_csv(file, &column, add, no_header, &Settings::load(options)?),
+        Some(Action::Json { field, add, file }) => {
+            let settings = Settings::load(options)?;
+            let mut out = BufWriter::new(io::stdout().lock());
+            write_result(convert_documents(
+                &mut open_input(file)?,
+                &mut out,
+                &field,
+                add,
+                &settings,
+            ))
+        }
+        Some(Action::Completions { shell }) => {
+            write_result(write_script(&shell, &mut io::stdout().lock()).map(|_| 0))
+        }
+        Some(Action::Repl) => repl::run(Settings::load(options)?),
+        Some(Action::Man) => write_result(write_man_page(&mut io::stdout().lock()).map(|_| 0)),
+        Some(Action::Explain { values }) => {
+            let settings = Settings {
+                verbose: true,
+                ..Settings::load(options)?
+            };
+            write_result(print_values(&values, &settings))
+        }
+        None => {
+            let settings = Settings::load(options)?;
+            match settings.output {
+                OutputMode::Json => {
+                    return values_result(print_json(&read_values(values)?, &settings)?);
+                }
+                // streamed line by line like text
+                OutputMode::Ndjson => return write_result(print_values(&values, &settings)),
+                OutputMode::Csv | OutputMode::Tsv | OutputMode::Markdown => {
+                    return values_result(print_table(&read_values(values)?, &settings));
+                }
+                OutputMode::Text => (),
+            }
+            // a template says exactly what each line holds, zones included
+            if !settings.zones.is_empty() && settings.template.is_none() {
+                let failures = print_zone_table(&read_values(values)?, &settings);
+                println!("Timezone: {}", settings.tz);
+                values_result(failures)
+            } else {
+                write_result(print_values(&values, &settings))
+            }
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    complete_from_env();
+    match execute_action(Cli::parse()) {
         Ok(resp) => {
             if let Some(msg) = resp {
                 println!("{}", msg);
             }
+            ExitCode::SUCCESS
         }
-        Err(e) => println!("Error: {}", e),
+        // every value that failed has already said why
+        Err(Failure::Values) => Failure::Values.exit_code(),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            e.exit_code()
+        }
+    }
+}
+
+#[cfg(test)]
+mod main_tests {
+    use super::convert_lines;
+    use crate::settings::{OutputMode, Settings};
+    use std::io::BufReader;
+
+    #[test]
+    fn test_convert_lines() {
+        let input = "1650627609\n\n  2022-04-22 11:40:09  \r\nbogus";
+        let mut out: Vec<u8> = Vec::new();
+        let failures = convert_lines(
+            &mut BufReader::new(input.as_bytes()),
+            &mut out,
+            &Settings::default(),
+        )
+        .unwrap();
+        // the failure itself is reported on stderr
+        assert_eq!(failures, 1);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1650627609 => 04-22-2022 11:40:09\n\
+             2022-04-22 11:40:09 => 1650627609\n"
+        );
+    }
+
+    #[test]
+    fn test_ndjson_lines() {
+        let settings = Settings {
+            output: OutputMode::Ndjson,
+            ..Default::default()
+        };
+        let mut out: Vec<u8> = Vec::new();
+        let failures = convert_lines(
+            &mut BufReader::new("1650627609\nbogus\n".as_bytes()),
+            &mut out,
+            &settings,
+        )
+        .unwrap();
+        assert_eq!(failures, 1);
+        let records: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["epoch"], 1650627609);
+        assert_eq!(records[1]["error"], "Invalid Pattern");
+    }
+
+    #[test]
+    fn test_fail_fast() {
+        let settings = Settings {
+            fail_fast: true,
+            ..Default::default()
+        };
+        let mut out: Vec<u8> = Vec::new();
+        let failures = convert_lines(
+            &mut BufReader::new("bogus\n1650627609\nbogus\n".as_bytes()),
+            &mut out,
+            &settings,
+        )
+        .unwrap();
+        assert_eq!(failures, 1);
+        assert!(out.is_empty());
     }
 }
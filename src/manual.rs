@@ -0,0 +1,216 @@
+// Reference sections shared by the long --help and the man page, built from the code they describe
+// so neither can drift from what rti actually accepts.
+
+use crate::cli::Cli;
+use crate::datetime_parsing::{DATE_PATTERNS, TIME_PATTERNS};
+use crate::failure::{CONFIG, IO, USAGE, VALUES};
+use chrono::NaiveDate;
+use clap::CommandFactory;
+use clap_mangen::Man;
+use directories::ProjectDirs;
+use itertools::Itertools;
+use roff::{bold, roman, Roff};
+use std::io::{self, Write};
+
+// 5/24/93 1:55:01.250 PM, the date the pattern comments use
+const EXAMPLE: (i32, u32, u32, u32, u32, u32, u32) = (1993, 5, 24, 13, 55, 1, 250);
+
+pub struct Section {
+    pub title: &'static str,
+    // (term, description)
+    pub entries: Vec<(String, String)>,
+}
+
+fn entry(term: &str, description: impl Into<String>) -> (String, String) {
+    (term.to_string(), description.into())
+}
+
+fn examples(patterns: &[&str]) -> String {
+    let (year, month, day, hour, minute, second, milli) = EXAMPLE;
+    let example = NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|date| date.and_hms_milli_opt(hour, minute, second, milli))
+        .expect("example date is valid");
+    patterns
+        .iter()
+        .map(|pattern| example.format(pattern).to_string().trim().to_string())
+        .unique()
+        .join(", ")
+}
+
+pub fn config_path() -> String {
+    // where confy keeps the config, found the same way it does
+    match ProjectDirs::from("rs", "", "rti") {
+        Some(dirs) => dirs.config_dir().join("rti.toml").display().to_string(),
+        None => "rti.toml in the platform config directory".to_string(),
+    }
+}
+
+fn input_formats() -> Section {
+    let (date, time) = (DATE_PATTERNS[4], TIME_PATTERNS[9]);
+    Section {
+        title: "Input formats",
+        entries: vec![
+            entry(
+                "Epochs",
+                "Seconds, or milliseconds from 13 digits on. --unit reads them as s, ms, us or ns instead. \
+                 Negative epochs, 0x, 0o and 0b prefixes and raw bytes such as \"99 a3 62 62\" work too.",
+            ),
+            entry("ObjectIds", "24 hex digit MongoDB ObjectIds convert to the time they were created."),
+            entry(
+                "Dates",
+                format!("{}. Midnight in the timezone.", examples(&DATE_PATTERNS)),
+            ),
+            entry(
+                "Times",
+                format!("{}. Today in the timezone.", examples(&TIME_PATTERNS)),
+            ),
+            entry(
+                "Date/times",
+                format!(
+                    "Any date and time above with a space between them, in either order, e.g. {} or {}.",
+                    examples(&[&format!("{} {}", date, time)]),
+                    examples(&[&format!("{} {}", TIME_PATTERNS[2], DATE_PATTERNS[2])]),
+                ),
+            ),
+            entry("Keywords", "now, yesterday and tomorrow."),
+            entry(
+                "Custom tokens",
+                "strftime patterns added with add-token, tried before everything else.",
+            ),
+        ],
+    }
+}
+
+fn timezone() -> Section {
+    Section {
+        title: "Timezone",
+        entries: vec![
+            entry(
+                "Precedence",
+                "--tz for one run, then the TIMEZONE env variable, then the timezone set with set-tz, then UTC.",
+            ),
+            entry(
+                "Names",
+                "An IANA name such as America/New_York, a fixed offset such as +05:30 or UTC-3, \
+                 or a POSIX TZ rule such as EST5EDT,M3.2.0,M11.1.0.",
+            ),
+        ],
+    }
+}
+
+fn files() -> Section {
+    Section {
+        title: "Files",
+        entries: vec![entry(
+            &config_path(),
+            "Config written by the set-, clear-, add- and remove- commands. Flags always override it.",
+        )],
+    }
+}
+
+fn exit_status() -> Section {
+    Section {
+        title: "Exit status",
+        entries: vec![
+            entry("0", "Every value converted."),
+            entry(
+                &VALUES.to_string(),
+                "At least one value couldn't be parsed.",
+            ),
+            entry(
+                &USAGE.to_string(),
+                "Usage error, such as an unknown flag or an invalid --tz.",
+            ),
+            entry(
+                &CONFIG.to_string(),
+                "Config error, such as an unreadable config file or a bad TIMEZONE.",
+            ),
+            entry(&IO.to_string(), "Reading input or writing output failed."),
+        ],
+    }
+}
+
+pub fn sections() -> Vec<Section> {
+    vec![input_formats(), timezone(), files(), exit_status()]
+}
+
+pub fn long_help() -> String {
+    // laid out like clap's own long help for options
+    let mut lines: Vec<String> = Vec::new();
+    for section in sections() {
+        lines.push(format!("{}:", section.title));
+        for (term, description) in section.entries {
+            lines.push(format!("  {}", term));
+            lines.push(format!("          {}", description));
+            lines.push(String::new());
+        }
+    }
+    lines.push(
+        "Use -- before values that look like commands or options, e.g. rti -- help.".to_string(),
+    );
+    lines.join("\n")
+}
+
+pub fn write_man_page<W: Write>(out: &mut W) -> io::Result<()> {
+    let man = Man::new(Cli::command());
+    man.render_title(out)?;
+    man.render_name_section(out)?;
+    man.render_synopsis_section(out)?;
+    man.render_description_section(out)?;
+    man.render_options_section(out)?;
+    man.render_subcommands_section(out)?;
+    let mut roff = Roff::default();
+    for section in sections() {
+        roff.control("SH", [section.title.to_uppercase().as_str()]);
+        for (term, description) in section.entries {
+            roff.control("TP", []);
+            roff.text([bold(term)]);
+            roff.text([roman(description)]);
+        }
+    }
+    roff.to_writer(out)?;
+    man.render_version_section(out)?;
+    man.render_authors_section(out)
+}
+
+#[cfg(test)]
+mod manual_tests {
+    use super::{examples, long_help, write_man_page};
+    use crate::datetime_parsing::{DATE_PATTERNS, TIME_PATTERNS};
+
+    #[test]
+    fn test_examples() {
+        assert_eq!(
+            examples(&DATE_PATTERNS),
+            "05-24-93, 05-24-1993, 05/24/93, 05/24/1993, 1993-05-24, 24-May-1993"
+        );
+        assert!(examples(&TIME_PATTERNS).starts_with("01:55 pm, 01:55 PM, 1:55 pm, 1:55 PM, 13:55"));
+    }
+
+    #[test]
+    fn test_long_help() {
+        let help = long_help();
+        assert!(help.contains("Input formats:\n  Epochs\n"));
+        assert!(help.contains("1993-05-24 13:55:01"));
+        assert!(help.contains("rti.toml"));
+    }
+
+    #[test]
+    fn test_man_page() {
+        let mut out: Vec<u8> = Vec::new();
+        write_man_page(&mut out).unwrap();
+        let page = String::from_utf8(out).unwrap();
+        assert!(page.contains(".TH rti 1"));
+        for heading in [
+            ".SH SYNOPSIS",
+            ".SH OPTIONS",
+            ".SH SUBCOMMANDS",
+            ".SH \"INPUT FORMATS\"",
+            ".SH TIMEZONE",
+            ".SH FILES",
+            ".SH \"EXIT STATUS\"",
+        ] {
+            assert!(page.contains(heading), "{}", heading);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+// MongoDB ObjectIds are 12 bytes shown as 24 hex characters. The first 4 bytes
+// are a big-endian Unix timestamp in seconds, the rest is a random value and counter.
+
+const OBJECT_ID_LEN: usize = 24;
+const TIMESTAMP_HEX_LEN: usize = 8;
+
+pub fn is_object_id(arg: &str) -> bool {
+    arg.len() == OBJECT_ID_LEN && arg.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+pub fn object_id_to_epoch(arg: &str) -> Result<i64, String> {
+    if !is_object_id(arg) {
+        return Err("ObjectIds must be 24 hex characters.".to_string());
+    }
+    match u32::from_str_radix(&arg[..TIMESTAMP_HEX_LEN], 16) {
+        Ok(val) => Ok(val as i64),
+        Err(e) => Err(format!("Error parsing ObjectId: {}", e)),
+    }
+}
+
+pub fn epoch_to_object_id(epoch: i64) -> Result<String, String> {
+    // Timestamp followed by zeroed bytes: the lowest ObjectId for that second.
+    match u32::try_from(epoch) {
+        Ok(val) => Ok(format!(
+            "{:08x}{}",
+            val,
+            "0".repeat(OBJECT_ID_LEN - TIMESTAMP_HEX_LEN)
+        )),
+        Err(_) => Err("ObjectId timestamps must be between 1970 and 2106.".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod object_id_tests {
+    use super::{epoch_to_object_id, is_object_id, object_id_to_epoch};
+
+    #[test]
+    fn test_is_object_id() {
+        assert!(is_object_id("6262a399f1b2c3d4e5f60718"));
+        assert!(is_object_id("6262A399F1B2C3D4E5F60718"));
+        assert!(!is_object_id("6262a399"));
+        assert!(!is_object_id("6262a399f1b2c3d4e5f6071z"));
+    }
+
+    #[test]
+    fn test_object_id_to_epoch() {
+        assert_eq!(
+            object_id_to_epoch("6262a399f1b2c3d4e5f60718"),
+            Ok(1650631577)
+        );
+    }
+
+    #[test]
+    fn test_epoch_to_object_id() {
+        assert_eq!(
+            epoch_to_object_id(1650631577),
+            Ok("6262a3990000000000000000".to_string())
+        );
+        assert!(epoch_to_object_id(-1).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+// How a converted instant is rendered: either a user supplied strftime string or
+// one of the named standards, which chrono can't express with strftime alone
+// (Z for UTC in ISO 8601, GMT-only HTTP dates).
+
+use crate::datetime_parsing::DATETIME_PARSE_FORMAT;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Locale, SecondsFormat, TimeZone, Utc};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+}
+
+impl Precision {
+    pub fn from_string(input: &str) -> Option<Precision> {
+        match input {
+            "s" => Some(Precision::Seconds),
+            "ms" => Some(Precision::Millis),
+            "us" => Some(Precision::Micros),
+            "ns" => Some(Precision::Nanos),
+            _ => None,
+        }
+    }
+
+    fn seconds_format(&self) -> SecondsFormat {
+        match self {
+            Precision::Seconds => SecondsFormat::Secs,
+            Precision::Millis => SecondsFormat::Millis,
+            Precision::Micros => SecondsFormat::Micros,
+            Precision::Nanos => SecondsFormat::Nanos,
+        }
+    }
+
+    fn strftime_fraction(&self) -> &'static str {
+        match self {
+            Precision::Seconds => "",
+            Precision::Millis => "%.3f",
+            Precision::Micros => "%.6f",
+            Precision::Nanos => "%.9f",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Iso,
+    Rfc3339,
+    Rfc2822,
+    Http,
+    Sql,
+    UnixDate,
+}
+
+impl Preset {
+    pub fn from_string(input: &str) -> Option<Preset> {
+        match input {
+            "iso" | "iso8601" => Some(Preset::Iso),
+            "rfc3339" => Some(Preset::Rfc3339),
+            "rfc2822" => Some(Preset::Rfc2822),
+            "http" => Some(Preset::Http),
+            "sql" => Some(Preset::Sql),
+            "unix-date" => Some(Preset::UnixDate),
+            _ => None,
+        }
+    }
+
+    fn allows_fractions(&self) -> bool {
+        matches!(self, Preset::Iso | Preset::Rfc3339 | Preset::Sql)
+    }
+}
+
+// What a locale shows when no format is given: its own date and time representation
+pub const LOCALE_DEFAULT_FORMAT: &str = "%c";
+
+pub fn parse_locale(input: &str) -> Result<Locale, String> {
+    // Accept fr_FR, fr-FR and the fr_FR.UTF-8 form found in LANG
+    let name = input.split('.').next().unwrap_or(input).replace('-', "_");
+    match Locale::try_from(name.as_str()) {
+        Ok(locale) => Ok(locale),
+        Err(_) => Err(format!(
+            "Invalid locale: {}. Use a name like fr_FR, de_DE or ja_JP.",
+            input
+        )),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputFormat {
+    Strftime(String),
+    // strftime with month and weekday names, %c, %x and %X from a locale
+    Localized(String, Locale),
+    Preset(Preset, Precision),
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::Strftime(DATETIME_PARSE_FORMAT.to_string())
+    }
+}
+
+impl OutputFormat {
+    pub fn parse(input: &str) -> Result<OutputFormat, String> {
+        // A preset name with an optional precision suffix (iso:ms), otherwise strftime.
+        let (name, precision) = match input.split_once(':') {
+            Some((name, precision)) => (name, Some(precision)),
+            None => (input, None),
+        };
+        if let Some(preset) = Preset::from_string(name) {
+            let precision = match precision {
+                None => Precision::Seconds,
+                Some(val) => match Precision::from_string(val) {
+                    Some(_) if !preset.allows_fractions() => {
+                        return Err(format!("{} does not support fractional seconds.", name))
+                    }
+                    Some(precision) => precision,
+                    None => {
+                        return Err(format!(
+                            "Invalid precision: {}. Use one of s, ms, us or ns.",
+                            val
+                        ))
+                    }
+                },
+            };
+            return Ok(OutputFormat::Preset(preset, precision));
+        }
+
+        // chrono panics when displaying a bad format, so check it before using or saving it
+        if input.is_empty() {
+            return Err("Output format cannot be empty.".to_string());
+        }
+        if StrftimeItems::new(input).any(|item| item == Item::Error) {
+            return Err(format!("Invalid output format: {}", input));
+        }
+        Ok(OutputFormat::Strftime(input.to_string()))
+    }
+
+    pub fn localized(self, locale: Locale) -> OutputFormat {
+        // Presets are fixed standards, so they stay in English whatever the locale
+        match self {
+            OutputFormat::Strftime(format) | OutputFormat::Localized(format, _) => {
+                OutputFormat::Localized(format, locale)
+            }
+            preset => preset,
+        }
+    }
+
+    pub fn format<T: TimeZone>(&self, datetime: &DateTime<T>) -> String
+    where
+        T::Offset: Display,
+    {
+        let (preset, precision) = match self {
+            OutputFormat::Strftime(format) => return datetime.format(format).to_string(),
+            OutputFormat::Localized(format, locale) => {
+                return datetime.format_localized(format, *locale).to_string()
+            }
+            OutputFormat::Preset(preset, precision) => (preset, precision),
+        };
+        match preset {
+            Preset::Iso => datetime.to_rfc3339_opts(precision.seconds_format(), true),
+            Preset::Rfc3339 => datetime.to_rfc3339_opts(precision.seconds_format(), false),
+            // the same layout as to_rfc2822, which panics outside the years 0 to 9999
+            Preset::Rfc2822 => datetime.format("%a, %-d %b %Y %H:%M:%S %z").to_string(),
+            // HTTP dates are always GMT, whatever the zone (RFC 9110 IMF-fixdate)
+            Preset::Http => datetime
+                .with_timezone(&Utc)
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string(),
+            Preset::Sql => datetime
+                .format(&format!(
+                    "%Y-%m-%d %H:%M:%S{}",
+                    precision.strftime_fraction()
+                ))
+                .to_string(),
+            Preset::UnixDate => datetime.format("%a %b %e %H:%M:%S %Z %Y").to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod output_format_tests {
+    use super::{parse_locale, OutputFormat, Precision, Preset, LOCALE_DEFAULT_FORMAT};
+    use chrono::TimeZone;
+    use chrono_tz::{Asia::Kolkata, UTC};
+
+    const APRIL_TWENTY_SECOND: i64 = 1650627609;
+
+    fn format_utc(format: &str) -> String {
+        let datetime = UTC.timestamp_opt(APRIL_TWENTY_SECOND, 123456789).unwrap();
+        OutputFormat::parse(format).unwrap().format(&datetime)
+    }
+
+    fn format_kolkata(format: &str) -> String {
+        let datetime = Kolkata
+            .timestamp_opt(APRIL_TWENTY_SECOND, 123456789)
+            .unwrap();
+        OutputFormat::parse(format).unwrap().format(&datetime)
+    }
+
+    #[test]
+    fn test_default_format() {
+        let datetime = UTC.timestamp_opt(APRIL_TWENTY_SECOND, 0).unwrap();
+        assert_eq!(
+            OutputFormat::default().format(&datetime),
+            "04-22-2022 11:40:09"
+        );
+    }
+
+    #[test]
+    fn test_iso() {
+        assert_eq!(format_utc("iso"), "2022-04-22T11:40:09Z");
+        assert_eq!(format_utc("iso:ms"), "2022-04-22T11:40:09.123Z");
+        assert_eq!(format_kolkata("iso:us"), "2022-04-22T17:10:09.123456+05:30");
+    }
+
+    #[test]
+    fn test_rfc3339() {
+        assert_eq!(format_utc("rfc3339"), "2022-04-22T11:40:09+00:00");
+        assert_eq!(
+            format_kolkata("rfc3339:ns"),
+            "2022-04-22T17:10:09.123456789+05:30"
+        );
+    }
+
+    #[test]
+    fn test_rfc2822() {
+        assert_eq!(format_utc("rfc2822"), "Fri, 22 Apr 2022 11:40:09 +0000");
+        assert_eq!(format_kolkata("rfc2822"), "Fri, 22 Apr 2022 17:10:09 +0530");
+        let format = OutputFormat::parse("rfc2822").unwrap();
+        assert_eq!(
+            format.format(&UTC.timestamp_opt(-62135596800, 0).unwrap()),
+            "Mon, 1 Jan 0001 00:00:00 +0000"
+        );
+        assert_eq!(
+            format.format(&UTC.timestamp_opt(253402300800, 0).unwrap()),
+            "Sat, 1 Jan +10000 00:00:00 +0000"
+        );
+    }
+
+    #[test]
+    fn test_http_is_always_gmt() {
+        assert_eq!(format_kolkata("http"), "Fri, 22 Apr 2022 11:40:09 GMT");
+    }
+
+    #[test]
+    fn test_sql() {
+        assert_eq!(format_utc("sql"), "2022-04-22 11:40:09");
+        assert_eq!(format_kolkata("sql:ms"), "2022-04-22 17:10:09.123");
+    }
+
+    #[test]
+    fn test_unix_date() {
+        assert_eq!(format_kolkata("unix-date"), "Fri Apr 22 17:10:09 IST 2022");
+    }
+
+    #[test]
+    fn test_parse_presets() {
+        assert_eq!(
+            OutputFormat::parse("iso8601:ns"),
+            Ok(OutputFormat::Preset(Preset::Iso, Precision::Nanos))
+        );
+        assert!(OutputFormat::parse("http:ms").is_err());
+        assert!(OutputFormat::parse("iso:minutes").is_err());
+    }
+
+    #[test]
+    fn test_parse_strftime() {
+        assert_eq!(
+            OutputFormat::parse("%d.%m.%Y %H:%M"),
+            Ok(OutputFormat::Strftime("%d.%m.%Y %H:%M".to_string()))
+        );
+        assert!(OutputFormat::parse("").is_err());
+        assert!(OutputFormat::parse("%Y-%m-%d %Q").is_err());
+        assert!(OutputFormat::parse("%").is_err());
+    }
+
+    fn format_localized(format: &str, locale: &str) -> String {
+        let datetime = UTC.timestamp_opt(APRIL_TWENTY_SECOND, 0).unwrap();
+        OutputFormat::parse(format)
+            .unwrap()
+            .localized(parse_locale(locale).unwrap())
+            .format(&datetime)
+    }
+
+    #[test]
+    fn test_localized_names() {
+        assert_eq!(format_localized("%e %B %Y", "fr_FR"), "22 avril 2022");
+        assert_eq!(format_localized("%A", "de-DE"), "Freitag");
+        assert_eq!(format_localized("%A", "ja_JP.UTF-8"), "金曜日");
+    }
+
+    #[test]
+    fn test_locale_default_format() {
+        assert_eq!(
+            format_localized(LOCALE_DEFAULT_FORMAT, "de_DE"),
+            "Fr 22 Apr 2022 11:40:09 UTC"
+        );
+    }
+
+    #[test]
+    fn test_presets_ignore_locale() {
+        assert_eq!(
+            format_localized("rfc2822", "fr_FR"),
+            "Fri, 22 Apr 2022 11:40:09 +0000"
+        );
+    }
+
+    #[test]
+    fn test_invalid_locale() {
+        assert!(parse_locale("xx_YY").is_err());
+    }
+}
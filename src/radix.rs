@@ -0,0 +1,175 @@
+// Epochs as they show up in packet captures, kernel structs and firmware dumps:
+// 0x/0o/0b prefixed integers and raw byte sequences like "99 a3 62 62",
+// "99:a3:62:62" or "\x99\xa3\x62\x62".
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    #[default]
+    Decimal,
+    Hex,
+    Octal,
+    Binary,
+}
+
+impl Radix {
+    pub fn from_string(input: &str) -> Option<Radix> {
+        match input {
+            "dec" | "decimal" => Some(Radix::Decimal),
+            "hex" => Some(Radix::Hex),
+            "oct" | "octal" => Some(Radix::Octal),
+            "bin" | "binary" => Some(Radix::Binary),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    #[default]
+    Big,
+    Little,
+}
+
+impl Endian {
+    pub fn from_string(input: &str) -> Option<Endian> {
+        match input {
+            "big" | "be" => Some(Endian::Big),
+            "little" | "le" => Some(Endian::Little),
+            _ => None,
+        }
+    }
+}
+
+// Fewer than 4 bytes can't hold a useful epoch and would clash with times like 13:55:01.
+const MIN_BYTES: usize = 4;
+const MAX_BYTES: usize = 8;
+
+fn parse_prefixed(arg: &str) -> Option<i64> {
+    let (negative, unsigned) = match arg.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, arg),
+    };
+    if unsigned.len() < 3 {
+        return None;
+    }
+    // get rather than indexing, the input may start with a multibyte character
+    let radix = match unsigned.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0o" | "0O") => 8,
+        Some("0b" | "0B") => 2,
+        _ => return None,
+    };
+    let digits = &unsigned[2..];
+    if digits.starts_with(['+', '-']) {
+        return None;
+    }
+    let magnitude = i64::from_str_radix(digits, radix).ok()?;
+    match negative {
+        true => Some(-magnitude),
+        false => Some(magnitude),
+    }
+}
+
+fn parse_byte_sequence(arg: &str, endian: Endian) -> Option<i64> {
+    // Bytes must be separated (or \x escaped) so they can't be mistaken for a decimal epoch.
+    let byte_strs: Vec<&str> = if arg.starts_with("\\x") {
+        arg.split("\\x").skip(1).collect()
+    } else if arg.contains(':') {
+        arg.split(':').collect()
+    } else if arg.contains(' ') {
+        arg.split_whitespace().collect()
+    } else {
+        return None;
+    };
+    if byte_strs.len() < MIN_BYTES || byte_strs.len() > MAX_BYTES {
+        return None;
+    }
+    let mut bytes: Vec<u8> = Vec::with_capacity(byte_strs.len());
+    for byte_str in byte_strs {
+        if byte_str.len() != 2 {
+            return None;
+        }
+        bytes.push(u8::from_str_radix(byte_str, 16).ok()?);
+    }
+    if endian == Endian::Little {
+        bytes.reverse();
+    }
+    let value = bytes
+        .iter()
+        .fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+    i64::try_from(value).ok()
+}
+
+pub fn is_decimal(arg: &str) -> bool {
+    // Looks like a decimal integer, whether or not it fits in an i64
+    let digits = arg.strip_prefix('-').unwrap_or(arg);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+pub fn parse_int(arg: &str, endian: Endian) -> Option<i64> {
+    // Decimal, prefixed or raw bytes. None if arg isn't any kind of integer.
+    if let Ok(val) = arg.parse::<i64>() {
+        return Some(val);
+    }
+    parse_prefixed(arg).or_else(|| parse_byte_sequence(arg, endian))
+}
+
+pub fn format_int(value: i64, radix: Radix) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    let magnitude = value.unsigned_abs();
+    match radix {
+        Radix::Decimal => value.to_string(),
+        Radix::Hex => format!("{}{:#x}", sign, magnitude),
+        Radix::Octal => format!("{}{:#o}", sign, magnitude),
+        Radix::Binary => format!("{}{:#b}", sign, magnitude),
+    }
+}
+
+#[cfg(test)]
+mod radix_tests {
+    use super::{format_int, parse_int, Endian, Radix};
+
+    #[test]
+    fn test_prefixed_input() {
+        assert_eq!(parse_int("0x6262a399", Endian::Big), Some(1650631577));
+        assert_eq!(parse_int("0X6262A399", Endian::Big), Some(1650631577));
+        assert_eq!(parse_int("0o14230521631", Endian::Big), Some(1650631577));
+        assert_eq!(
+            parse_int("0b1100010011000101010001110011001", Endian::Big),
+            Some(1650631577)
+        );
+        assert_eq!(parse_int("-0x10", Endian::Big), Some(-16));
+    }
+
+    #[test]
+    fn test_byte_sequences() {
+        assert_eq!(parse_int("62 62 a3 99", Endian::Big), Some(1650631577));
+        assert_eq!(parse_int("99:a3:62:62", Endian::Little), Some(1650631577));
+        assert_eq!(
+            parse_int("\\x99\\xa3\\x62\\x62", Endian::Little),
+            Some(1650631577)
+        );
+    }
+
+    #[test]
+    fn test_not_an_int() {
+        assert_eq!(parse_int("0x", Endian::Big), None);
+        assert_eq!(parse_int("13:55", Endian::Big), None);
+        assert_eq!(parse_int("€12", Endian::Big), None);
+        assert_eq!(parse_int("-€12", Endian::Big), None);
+        assert_eq!(parse_int("金曜日", Endian::Big), None);
+        assert_eq!(parse_int("é1 2é 34 56", Endian::Big), None);
+        assert_eq!(parse_int("13:55:01", Endian::Big), None);
+        assert_eq!(parse_int("5-1-93", Endian::Big), None);
+        assert_eq!(parse_int("01 23 PM", Endian::Big), None);
+    }
+
+    #[test]
+    fn test_format_int() {
+        assert_eq!(format_int(1650631577, Radix::Hex), "0x6262a399");
+        assert_eq!(format_int(1650631577, Radix::Octal), "0o14230521631");
+        assert_eq!(format_int(5, Radix::Binary), "0b101");
+        assert_eq!(format_int(-16, Radix::Hex), "-0x10");
+        assert_eq!(format_int(42, Radix::Decimal), "42");
+    }
+}
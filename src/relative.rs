@@ -0,0 +1,163 @@
+// How far an instant is from now (or another reference), e.g. "3 days ago" or "in 2d 3h 4m 5s".
+
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelativeMode {
+    // rounded to this many units, largest first
+    Human(usize),
+    Exact,
+}
+
+impl RelativeMode {
+    pub fn from_string(input: &str, granularity: usize) -> Option<RelativeMode> {
+        match input {
+            "human" => Some(RelativeMode::Human(granularity)),
+            "exact" => Some(RelativeMode::Exact),
+            _ => None,
+        }
+    }
+}
+
+impl Default for RelativeMode {
+    fn default() -> Self {
+        RelativeMode::Human(1)
+    }
+}
+
+// Months and years are approximate, which is fine for a rounded description
+const HUMAN_UNITS: [(i64, &str); 6] = [
+    (365 * 86400, "year"),
+    (30 * 86400, "month"),
+    (86400, "day"),
+    (3600, "hour"),
+    (60, "minute"),
+    (1, "second"),
+];
+
+// asking for more units than there are just shows all of them
+pub const MAX_GRANULARITY: usize = HUMAN_UNITS.len();
+
+const EXACT_UNITS: [(i64, &str); 4] = [(86400, "d"), (3600, "h"), (60, "m"), (1, "s")];
+
+fn largest_unit(seconds: i64) -> usize {
+    HUMAN_UNITS
+        .iter()
+        .position(|(size, _)| seconds >= *size)
+        .unwrap_or(HUMAN_UNITS.len() - 1)
+}
+
+fn humanize(seconds: i64, granularity: usize) -> String {
+    // Round to the smallest unit shown. Rounding can carry into a bigger unit
+    // (59m 50s is about an hour), so repeat until the largest unit stops growing.
+    let mut rounded = seconds;
+    let mut start = largest_unit(rounded);
+    loop {
+        let last = start
+            .saturating_add(granularity.max(1) - 1)
+            .min(HUMAN_UNITS.len() - 1);
+        let size = HUMAN_UNITS[last].0;
+        rounded = (seconds + size / 2) / size * size;
+        let new_start = largest_unit(rounded);
+        if new_start >= start {
+            break;
+        }
+        start = new_start;
+    }
+
+    let mut remaining = rounded;
+    let mut parts: Vec<String> = Vec::new();
+    for (size, name) in HUMAN_UNITS.iter().skip(start).take(granularity.max(1)) {
+        let count = remaining / size;
+        remaining %= size;
+        if count > 0 {
+            let plural = if count == 1 { "" } else { "s" };
+            parts.push(format!("{} {}{}", count, name, plural));
+        }
+    }
+    parts.join(" ")
+}
+
+fn exact(seconds: i64) -> String {
+    let mut remaining = seconds;
+    let mut parts: Vec<String> = Vec::new();
+    for (size, suffix) in EXACT_UNITS {
+        let count = remaining / size;
+        remaining %= size;
+        if count > 0 {
+            parts.push(format!("{}{}", count, suffix));
+        }
+    }
+    parts.join(" ")
+}
+
+pub fn relative(instant: &DateTime<Utc>, reference: &DateTime<Utc>, mode: RelativeMode) -> String {
+    let difference = instant.signed_duration_since(*reference).num_seconds();
+    let seconds = difference.saturating_abs();
+    let described = match mode {
+        RelativeMode::Human(granularity) => humanize(seconds, granularity),
+        RelativeMode::Exact => exact(seconds),
+    };
+    if described.is_empty() {
+        return "now".to_string();
+    }
+    match difference < 0 {
+        true => format!("{} ago", described),
+        false => format!("in {}", described),
+    }
+}
+
+#[cfg(test)]
+mod relative_tests {
+    use super::{relative, RelativeMode};
+    use chrono::{DateTime, TimeZone, Utc};
+
+    const REFERENCE: i64 = 1650627609;
+
+    fn from_reference(offset: i64, mode: RelativeMode) -> String {
+        let reference: DateTime<Utc> = Utc.timestamp_opt(REFERENCE, 0).unwrap();
+        let instant = Utc.timestamp_opt(REFERENCE + offset, 0).unwrap();
+        relative(&instant, &reference, mode)
+    }
+
+    #[test]
+    fn test_human() {
+        let mode = RelativeMode::Human(1);
+        assert_eq!(from_reference(-3 * 86400, mode), "3 days ago");
+        assert_eq!(from_reference(3600, mode), "in 1 hour");
+        assert_eq!(from_reference(-45, mode), "45 seconds ago");
+        assert_eq!(from_reference(0, mode), "now");
+        assert_eq!(from_reference(400 * 86400, mode), "in 1 year");
+    }
+
+    #[test]
+    fn test_huge_granularity() {
+        let mode = RelativeMode::Human(usize::MAX);
+        assert_eq!(from_reference(-3 * 86400 - 5, mode), "3 days 5 seconds ago");
+    }
+
+    #[test]
+    fn test_human_rounding() {
+        // 2h 15m 40s
+        assert_eq!(
+            from_reference(8140, RelativeMode::Human(2)),
+            "in 2 hours 16 minutes"
+        );
+        assert_eq!(from_reference(8140, RelativeMode::Human(1)), "in 2 hours");
+        // 59m 50s rounds up into the next unit
+        assert_eq!(from_reference(-3590, RelativeMode::Human(1)), "1 hour ago");
+        // 23h 59m 59s
+        assert_eq!(from_reference(86399, RelativeMode::Human(2)), "in 1 day");
+    }
+
+    #[test]
+    fn test_exact() {
+        let two_days_three_hours = 2 * 86400 + 3 * 3600 + 4 * 60 + 5;
+        assert_eq!(
+            from_reference(-two_days_three_hours, RelativeMode::Exact),
+            "2d 3h 4m 5s ago"
+        );
+        assert_eq!(from_reference(3605, RelativeMode::Exact), "in 1h 5s");
+        assert_eq!(from_reference(0, RelativeMode::Exact), "now");
+    }
+}
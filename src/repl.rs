@@ -0,0 +1,281 @@
+// Interactive conversions with `rti repl`. Settings are loaded once for the session
+// and changed with :commands, so each line converts without touching the config.
+
+use crate::conversion::{convert, Conversion, InputKind};
+use crate::datetime_parsing::EpochUnit;
+use crate::failure::{Failure, OkOrFailure};
+use crate::output_format::OutputFormat;
+use crate::settings::Settings;
+use crate::timezone::Zone;
+use chrono::{DateTime, Duration, Utc};
+use directories::ProjectDirs;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+const PROMPT: &str = "rti> ";
+const PREVIOUS: &str = "_";
+
+const HELP: &str = "Enter any value to convert it. Session commands:
+  :tz <zone>      Change the timezone, e.g. :tz Asia/Tokyo
+  :fmt <format>   Change the output format, a preset such as iso or a strftime string
+  :unit <unit>    Change the epoch unit: auto, s, ms, us or ns
+  :quit           Leave the session, as does Ctrl-D
+Each command without an argument shows the current value.
+_ is the previous result. Shift any value with + or -, e.g. _ + 1d or now - 90m.
+Amounts take a unit of ns, us, ms, s, m, h, d or w, and plain numbers are seconds.";
+
+fn parse_amount(token: &str) -> Option<Duration> {
+    let digits = token
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(token.len());
+    let amount: i64 = token[..digits].parse().ok()?;
+    match &token[digits..] {
+        "ns" => Some(Duration::nanoseconds(amount)),
+        "us" => Some(Duration::microseconds(amount)),
+        "ms" => Some(Duration::milliseconds(amount)),
+        "" | "s" => Duration::try_seconds(amount),
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
+fn split_shifts(line: &str) -> (String, Vec<Duration>) {
+    // Peels "+ 1d" and "- 90m" off the end, so values with spaces or dashes in them stay whole
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+    let mut shifts: Vec<Duration> = Vec::new();
+    while words.len() > 2 {
+        let amount = match parse_amount(words[words.len() - 1]) {
+            Some(val) => val,
+            None => break,
+        };
+        match words[words.len() - 2] {
+            "+" => shifts.push(amount),
+            "-" => shifts.push(-amount),
+            _ => break,
+        }
+        words.truncate(words.len() - 2);
+    }
+    match shifts.is_empty() {
+        true => (line.to_string(), shifts),
+        false => (words.join(" "), shifts),
+    }
+}
+
+pub struct Session {
+    pub settings: Settings,
+    // the kind and instant of the previous result, for _
+    previous: Option<(InputKind, DateTime<Utc>)>,
+}
+
+impl Session {
+    pub fn new(settings: Settings) -> Session {
+        Session {
+            settings,
+            previous: None,
+        }
+    }
+
+    pub fn evaluate(&mut self, line: &str) -> Result<Conversion, String> {
+        let (value, shifts) = split_shifts(line);
+        let mut conversion = match (value.as_str(), self.previous) {
+            (PREVIOUS, Some((kind, instant))) => Conversion {
+                input: line.to_string(),
+                kind,
+                pattern: None,
+                instant,
+                notes: Vec::new(),
+            },
+            (PREVIOUS, None) => return Err("No previous result yet.".to_string()),
+            _ => convert(&value, &self.settings)?,
+        };
+        if !shifts.is_empty() {
+            for shift in shifts {
+                conversion.instant = conversion
+                    .instant
+                    .checked_add_signed(shift)
+                    .ok_or("Result is out of range.")?;
+            }
+            conversion.input = line.to_string();
+        }
+        self.previous = Some((conversion.kind, conversion.instant));
+        Ok(conversion)
+    }
+
+    pub fn command(&mut self, line: &str) -> Result<String, String> {
+        // `line` is everything after the colon
+        let (name, argument) = match line.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, Some(argument.trim())),
+            None => (line, None),
+        };
+        let settings = &mut self.settings;
+        match (name, argument) {
+            ("tz", Some(val)) => settings.tz = Zone::parse(val)?,
+            ("tz", None) => (),
+            ("fmt", Some(val)) => {
+                settings.format = match settings.locale {
+                    Some(locale) => OutputFormat::parse(val)?.localized(locale),
+                    None => OutputFormat::parse(val)?,
+                }
+            }
+            ("fmt", None) => (),
+            ("unit", Some(val)) => {
+                settings.unit = EpochUnit::from_string(val)
+                    .ok_or(format!("Invalid unit: {}. Use auto, s, ms, us or ns.", val))?
+            }
+            ("unit", None) => (),
+            ("help", _) => return Ok(HELP.to_string()),
+            _ => return Err(format!("Unknown command: :{}. See :help.", line)),
+        }
+        Ok(match name {
+            "tz" => format!("Timezone: {}", settings.tz),
+            // a format is easiest to recognise by what it prints
+            "fmt" => format!(
+                "Format: {}",
+                settings
+                    .format
+                    .format(&Utc::now().with_timezone(&settings.tz))
+            ),
+            _ => format!("Unit: {}", settings.unit.name()),
+        })
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    ProjectDirs::from("rs", "", "rti").map(|dirs| dirs.data_dir().join("history.txt"))
+}
+
+pub fn run(settings: Settings) -> OkOrFailure {
+    let mut editor =
+        DefaultEditor::new().map_err(|e| Failure::Io(format!("Unable to start repl: {}", e)))?;
+    let history = history_path();
+    if let Some(path) = &history {
+        // there's no history the first time
+        let _ = editor.load_history(path);
+    }
+    println!("Timezone: {}. Type :help for commands.", settings.tz);
+    let mut session = Session::new(settings);
+    let mut out = io::stdout();
+    loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(val) => val,
+            // Ctrl-C drops the line like a shell, Ctrl-D leaves
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(Failure::Io(format!("Unable to read input: {}", e))),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+        let result = match line.strip_prefix(':') {
+            Some("q" | "quit") => break,
+            Some(command) => session.command(command).map(|val| println!("{}", val)),
+            None => match session.evaluate(line) {
+                Ok(conversion) => crate::write_conversion(&mut out, &conversion, &session.settings)
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(format!("Unable to parse value: {} ({})", line, e)),
+            },
+        };
+        if let Err(e) = result {
+            eprintln!("{}", e);
+        }
+    }
+    if let Some(path) = &history {
+        let saved = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .map_err(ReadlineError::Io)
+            .and_then(|_| editor.save_history(path));
+        if let Err(e) = saved {
+            eprintln!("Unable to save history to {}: {}", path.display(), e);
+        }
+    }
+    out.flush().map_err(|e| Failure::Io(e.to_string()))?;
+    Ok(None)
+}
+
+#[cfg(test)]
+mod repl_tests {
+    use super::{parse_amount, split_shifts, Session};
+    use crate::settings::Settings;
+    use chrono::Duration;
+
+    fn outputs(session: &mut Session, lines: &[&str]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| match session.evaluate(line) {
+                Ok(conversion) => conversion.output(&session.settings),
+                Err(e) => e,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_shifts() {
+        assert_eq!(parse_amount("90m"), Some(Duration::minutes(90)));
+        assert_eq!(parse_amount("250ms"), Some(Duration::milliseconds(250)));
+        assert_eq!(parse_amount("3600"), Some(Duration::seconds(3600)));
+        assert_eq!(parse_amount("1y"), None);
+        assert_eq!(
+            split_shifts("2022-04-22 11:40:09 + 1d - 2h"),
+            (
+                "2022-04-22 11:40:09".to_string(),
+                vec![-Duration::hours(2), Duration::days(1)]
+            )
+        );
+        assert_eq!(split_shifts("1:23 PM 5/24/93").1, vec![]);
+        assert_eq!(split_shifts("- 5").1, vec![]);
+    }
+
+    #[test]
+    fn test_previous_result() {
+        let mut session = Session::new(Settings::default());
+        assert_eq!(
+            outputs(
+                &mut session,
+                &["_", "1650627609", "_ + 1d", "_ - 30m", "bogus", "_"]
+            ),
+            vec![
+                "No previous result yet.",
+                "04-22-2022 11:40:09",
+                "04-23-2022 11:40:09",
+                "04-23-2022 11:10:09",
+                "Invalid Pattern",
+                "04-23-2022 11:10:09",
+            ]
+        );
+        // date/times come back as epochs, shifted or not
+        assert_eq!(
+            outputs(&mut session, &["2022-04-22 11:40:09 + 1h", "_ + 1s"]),
+            vec!["1650631209", "1650631210"]
+        );
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut session = Session::new(Settings::default());
+        assert_eq!(
+            session.command("tz Asia/Tokyo"),
+            Ok("Timezone: Asia/Tokyo".to_string())
+        );
+        assert_eq!(session.command("unit ms"), Ok("Unit: ms".to_string()));
+        assert!(session.command("fmt %F").unwrap().starts_with("Format: 20"));
+        assert_eq!(
+            outputs(&mut session, &["1650627609000", "2022-04-22"]),
+            vec!["2022-04-22", "1650553200000"]
+        );
+        assert!(session.command("tz Mars/Olympus").is_err());
+        assert!(session.command("unit days").is_err());
+        assert!(session.command("bogus").is_err());
+        assert_eq!(
+            session.command("tz"),
+            Ok("Timezone: Asia/Tokyo".to_string())
+        );
+    }
+}
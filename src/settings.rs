@@ -0,0 +1,210 @@
+use crate::cli::Options;
+use crate::config::{
+    get_custom_tokens, get_display_zones, get_locale, get_output_format, get_template, get_timezone,
+};
+use crate::conversion::convert;
+use crate::datetime_parsing::EpochUnit;
+use crate::failure::Failure;
+use crate::output_format::{parse_locale, OutputFormat, LOCALE_DEFAULT_FORMAT};
+use crate::radix::{Endian, Radix};
+use crate::relative::RelativeMode;
+use crate::table_output::{default_columns, Column};
+use crate::template::Template;
+use crate::timezone::Zone;
+use chrono::{DateTime, Locale, Utc};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    #[default]
+    Text,
+    Json,
+    Ndjson,
+    Csv,
+    Tsv,
+    Markdown,
+}
+
+impl OutputMode {
+    pub fn from_string(input: &str) -> Option<OutputMode> {
+        match input {
+            "text" => Some(OutputMode::Text),
+            "json" => Some(OutputMode::Json),
+            "ndjson" => Some(OutputMode::Ndjson),
+            "csv" => Some(OutputMode::Csv),
+            "tsv" => Some(OutputMode::Tsv),
+            "markdown" | "md" => Some(OutputMode::Markdown),
+            _ => None,
+        }
+    }
+}
+
+// Everything a conversion needs, resolved once per run from flags, env and config.
+pub struct Settings {
+    pub tz: Zone,
+    pub custom_tokens: Vec<String>,
+    pub format: OutputFormat,
+    pub radix: Radix,
+    pub endian: Endian,
+    // unit epochs are read in and written out in
+    pub unit: EpochUnit,
+    // when set, each conversion is shown in every one of these zones
+    pub zones: Vec<Zone>,
+    pub output: OutputMode,
+    // columns for csv, tsv and markdown output
+    pub columns: Vec<Column>,
+    // when set, text output also says how far each conversion is from the reference
+    pub relative: Option<RelativeMode>,
+    // instant relative output is measured from, now when unset
+    pub reference: Option<DateTime<Utc>>,
+    // show every conversion in seconds, milliseconds, microseconds and nanoseconds
+    pub units: bool,
+    // print a breakdown of each instant (weekday, ISO week, DST and so on)
+    pub verbose: bool,
+    // replaces the "input => output" line and the Timezone footer in text output
+    pub template: Option<Template>,
+    // month and weekday names for strftime formats, and the default format when none is set
+    pub locale: Option<Locale>,
+    // stop at the first value that can't be converted instead of carrying on
+    pub fail_fast: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            tz: Zone::default(),
+            custom_tokens: Vec::new(),
+            format: OutputFormat::default(),
+            radix: Radix::default(),
+            endian: Endian::default(),
+            unit: EpochUnit::default(),
+            zones: Vec::new(),
+            output: OutputMode::default(),
+            columns: default_columns(),
+            relative: None,
+            reference: None,
+            units: false,
+            verbose: false,
+            template: None,
+            locale: None,
+            fail_fast: false,
+        }
+    }
+}
+
+fn from_flag_or_config<T>(
+    flag: &Option<T>,
+    config: impl FnOnce() -> Result<Option<T>, Failure>,
+) -> Result<(Option<T>, bool), Failure>
+where
+    T: Clone,
+{
+    // the value and whether it came from the config file
+    match flag {
+        Some(val) => Ok((Some(val.clone()), false)),
+        None => Ok((config()?, true)),
+    }
+}
+
+fn checked<T>(result: Result<T, String>, from_config: bool) -> Result<T, Failure> {
+    // A bad value in the config file is a config error, the same value given as a flag is a usage error
+    result.map_err(|e| match from_config {
+        true => Failure::Config(format!("{} (from config)", e)),
+        false => Failure::Usage(e),
+    })
+}
+
+impl Settings {
+    pub fn load(options: &Options) -> Result<Settings, Failure> {
+        let (locale, from_config) = from_flag_or_config(&options.locale, get_locale)?;
+        let locale = match locale {
+            Some(val) => Some(checked(parse_locale(&val), from_config)?),
+            None => None,
+        };
+
+        // Output format precedence: flag, then config, then the default
+        let (format, from_config) = from_flag_or_config(&options.format, get_output_format)?;
+        let format = match (format, locale) {
+            (Some(val), Some(locale)) => {
+                checked(OutputFormat::parse(&val), from_config)?.localized(locale)
+            }
+            (Some(val), None) => checked(OutputFormat::parse(&val), from_config)?,
+            (None, Some(locale)) => {
+                OutputFormat::Localized(LOCALE_DEFAULT_FORMAT.to_string(), locale)
+            }
+            (None, None) => OutputFormat::default(),
+        };
+
+        let (zone_names, from_config) =
+            from_flag_or_config(&options.zones, || get_display_zones().map(Some))?;
+        let mut zones: Vec<Zone> = Vec::new();
+        for name in zone_names.unwrap_or_default() {
+            zones.push(checked(Zone::parse(&name), from_config)?);
+        }
+
+        let columns = match &options.columns {
+            Some(names) => names
+                .iter()
+                .map(|name| Column::from_string(name))
+                .collect::<Result<Vec<Column>, String>>()?,
+            None => default_columns(),
+        };
+
+        // Template precedence matches the output format: flag, then config
+        let (template, from_config) = from_flag_or_config(&options.template, get_template)?;
+        let template = match template {
+            Some(val) => Some(checked(Template::parse(&val), from_config)?),
+            None => None,
+        };
+
+        // --granularity or --relative-to on their own imply humanized output
+        let granularity = options.granularity.unwrap_or(1);
+        let relative = match &options.relative {
+            Some(val) => match RelativeMode::from_string(val, granularity) {
+                Some(mode) => Some(mode),
+                None => return Err(Failure::Usage(format!("Invalid relative mode: {}", val))),
+            },
+            None if options.granularity.is_some() || options.relative_to.is_some() => {
+                Some(RelativeMode::Human(granularity))
+            }
+            None => None,
+        };
+
+        // Timezone precedence: flag, then env, then config, then UTC
+        let tz = match &options.tz {
+            Some(val) => Zone::parse(val)?,
+            None => get_timezone()?,
+        };
+
+        let mut settings = Settings {
+            tz,
+            custom_tokens: get_custom_tokens()?,
+            format,
+            radix: options.radix,
+            endian: options.endian,
+            unit: options.unit,
+            zones,
+            output: options.output_mode(),
+            columns,
+            relative,
+            reference: None,
+            units: options.units,
+            verbose: options.verbose,
+            template,
+            locale,
+            fail_fast: options.fail_fast,
+        };
+        // the reference can be written any way a normal argument can
+        if let Some(val) = &options.relative_to {
+            match convert(val, &settings) {
+                Ok(conversion) => settings.reference = Some(conversion.instant),
+                Err(e) => {
+                    return Err(Failure::Usage(format!(
+                        "Invalid reference time: {} ({})",
+                        val, e
+                    )))
+                }
+            }
+        }
+        Ok(settings)
+    }
+}
@@ -0,0 +1,192 @@
+// Snowflake IDs are 64-bit integers laid out (from the most significant bit) as
+// an unused sign bit, a millisecond timestamp relative to a custom epoch, a
+// worker/machine id and a per-millisecond sequence number.
+// Services differ in their epoch and in how many bits go to worker and sequence,
+// so a layout only needs those three values; the timestamp takes the rest.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+const USABLE_BITS: u32 = 63; // sign bit is never set
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SnowflakeLayout {
+    pub name: String,
+    pub epoch_ms: i64,
+    pub worker_bits: u32,
+    pub sequence_bits: u32,
+}
+
+impl SnowflakeLayout {
+    pub fn new(name: &str, epoch_ms: i64, worker_bits: u32, sequence_bits: u32) -> Self {
+        SnowflakeLayout {
+            name: name.to_string(),
+            epoch_ms,
+            worker_bits,
+            sequence_bits,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("Snowflake layout name cannot be empty.".to_string());
+        }
+        if DateTime::<Utc>::from_timestamp_millis(self.epoch_ms).is_none() {
+            return Err(format!(
+                "Snowflake epoch {} ms is out of range.",
+                self.epoch_ms
+            ));
+        }
+        // checked, both counts come straight from the user
+        let combined = self.worker_bits.checked_add(self.sequence_bits);
+        if combined.is_none_or(|bits| bits >= USABLE_BITS) {
+            return Err(format!(
+                "Worker and sequence bits must leave room for a timestamp (at most {} combined).",
+                USABLE_BITS - 1
+            ));
+        }
+        Ok(())
+    }
+
+    fn timestamp_shift(&self) -> u32 {
+        self.worker_bits + self.sequence_bits
+    }
+}
+
+pub fn presets() -> Vec<SnowflakeLayout> {
+    vec![
+        SnowflakeLayout::new("twitter", 1288834974657, 10, 12),
+        SnowflakeLayout::new("discord", 1420070400000, 10, 12),
+        SnowflakeLayout::new("instagram", 1314220021721, 13, 10),
+        SnowflakeLayout::new("mastodon", 0, 0, 16),
+    ]
+}
+
+pub fn find_layout(name: &str, custom_layouts: &[SnowflakeLayout]) -> Option<SnowflakeLayout> {
+    // custom layouts are checked first so they can't be shadowed by a preset
+    custom_layouts
+        .iter()
+        .chain(presets().iter())
+        .find(|layout| layout.name == name)
+        .cloned()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Snowflake {
+    pub timestamp_ms: i64,
+    pub worker: u64,
+    pub sequence: u64,
+}
+
+fn mask(bits: u32) -> u64 {
+    (1u64 << bits) - 1
+}
+
+pub fn decode(id: u64, layout: &SnowflakeLayout) -> Result<Snowflake, String> {
+    if id >> USABLE_BITS != 0 {
+        return Err("Snowflake IDs cannot use the sign bit.".to_string());
+    }
+    let offset_ms = (id >> layout.timestamp_shift()) as i64;
+    let timestamp_ms = match layout.epoch_ms.checked_add(offset_ms) {
+        Some(val) => val,
+        None => return Err("Snowflake timestamp is out of range.".to_string()),
+    };
+    Ok(Snowflake {
+        timestamp_ms,
+        worker: (id >> layout.sequence_bits) & mask(layout.worker_bits),
+        sequence: id & mask(layout.sequence_bits),
+    })
+}
+
+pub fn lowest_for(epoch_ms: i64, layout: &SnowflakeLayout) -> Result<u64, String> {
+    // Smallest ID that could have been generated at epoch_ms: worker and sequence are zero.
+    if epoch_ms < layout.epoch_ms {
+        return Err(format!(
+            "Time is before the {} epoch ({} ms).",
+            layout.name, layout.epoch_ms
+        ));
+    }
+    // the difference overflows when it's beyond any timestamp the layout could hold anyway
+    let timestamp_bits = USABLE_BITS - layout.timestamp_shift();
+    let offset_ms = match epoch_ms.checked_sub(layout.epoch_ms) {
+        Some(val) if val as u64 <= mask(timestamp_bits) => val as u64,
+        _ => {
+            return Err(format!(
+                "Time is past the end of the {} layout.",
+                layout.name
+            ))
+        }
+    };
+    Ok(offset_ms << layout.timestamp_shift())
+}
+
+#[cfg(test)]
+mod snowflake_tests {
+    use super::{decode, find_layout, lowest_for, Snowflake, SnowflakeLayout};
+
+    #[test]
+    fn test_decode_twitter() {
+        let twitter = find_layout("twitter", &[]).unwrap();
+        assert_eq!(
+            decode(1541815603606036480, &twitter),
+            Ok(Snowflake {
+                timestamp_ms: 1656432460105,
+                worker: 378,
+                sequence: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_discord() {
+        let discord = find_layout("discord", &[]).unwrap();
+        assert_eq!(
+            decode(175928847299117063, &discord),
+            Ok(Snowflake {
+                timestamp_ms: 1462015105796,
+                worker: 32,
+                sequence: 7,
+            })
+        );
+    }
+
+    #[test]
+    fn test_lowest_for_round_trips() {
+        let twitter = find_layout("twitter", &[]).unwrap();
+        let lowest = lowest_for(1656432460105, &twitter).unwrap();
+        assert_eq!(lowest, 1541815603604488192);
+        assert_eq!(
+            decode(lowest, &twitter).unwrap().timestamp_ms,
+            1656432460105
+        );
+    }
+
+    #[test]
+    fn test_lowest_for_before_epoch() {
+        let discord = find_layout("discord", &[]).unwrap();
+        assert!(lowest_for(0, &discord).is_err());
+    }
+
+    #[test]
+    fn test_custom_layout_takes_precedence() {
+        let custom = vec![SnowflakeLayout::new("twitter", 0, 0, 22)];
+        assert_eq!(find_layout("twitter", &custom).unwrap().epoch_ms, 0);
+    }
+
+    #[test]
+    fn test_invalid_layout() {
+        assert!(SnowflakeLayout::new("huge", 0, 40, 30).validate().is_err());
+        assert!(SnowflakeLayout::new("wraps", 0, u32::MAX, 1)
+            .validate()
+            .is_err());
+        assert!(SnowflakeLayout::new("neg", i64::MIN, 0, 0)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_lowest_for_far_from_epoch() {
+        let layout = SnowflakeLayout::new("neg", i64::MIN, 0, 0);
+        assert!(lowest_for(1650627609000, &layout).is_err());
+    }
+}
@@ -0,0 +1,270 @@
+// Tabular output for spreadsheets and wiki pages: a header row and one row per argument.
+
+use crate::conversion::{convert, Conversion};
+use crate::radix::format_int;
+use crate::relative::relative;
+use crate::settings::{OutputMode, Settings};
+use crate::timezone::Zone;
+use crate::zones::zone_cell;
+use chrono::{SecondsFormat, Utc};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Input,
+    Kind,
+    Epoch,
+    Millis,
+    Micros,
+    Nanos,
+    Iso,
+    Local,
+    Relative,
+    // expands to one column per configured display zone
+    Zones,
+    Zone(Zone),
+    Error,
+}
+
+impl Column {
+    pub fn from_string(input: &str) -> Result<Column, String> {
+        match input {
+            "input" => Ok(Column::Input),
+            "kind" => Ok(Column::Kind),
+            "epoch" => Ok(Column::Epoch),
+            "ms" => Ok(Column::Millis),
+            "us" => Ok(Column::Micros),
+            "ns" => Ok(Column::Nanos),
+            "iso" => Ok(Column::Iso),
+            "local" => Ok(Column::Local),
+            "relative" => Ok(Column::Relative),
+            "zones" => Ok(Column::Zones),
+            "error" => Ok(Column::Error),
+            _ => match Zone::parse(input) {
+                Ok(zone) => Ok(Column::Zone(zone)),
+                Err(_) => Err(format!(
+                    "Invalid column: {}. Use input, kind, epoch, ms, us, ns, iso, local, relative, zones, error or a timezone.",
+                    input
+                )),
+            },
+        }
+    }
+
+    fn header(&self) -> String {
+        match self {
+            Column::Input => "input".to_string(),
+            Column::Kind => "kind".to_string(),
+            Column::Epoch => "epoch".to_string(),
+            Column::Millis => "ms".to_string(),
+            Column::Micros => "us".to_string(),
+            Column::Nanos => "ns".to_string(),
+            Column::Iso => "iso".to_string(),
+            Column::Local => "local".to_string(),
+            Column::Relative => "relative".to_string(),
+            Column::Zones => "zones".to_string(),
+            Column::Zone(zone) => zone.to_string(),
+            Column::Error => "error".to_string(),
+        }
+    }
+
+    fn cell(&self, conversion: &Conversion, settings: &Settings) -> String {
+        match self {
+            Column::Input => conversion.input.clone(),
+            Column::Kind => conversion.kind.name().to_string(),
+            Column::Epoch => format_int(conversion.epoch(), settings.radix),
+            Column::Millis => format_int(conversion.epoch_ms(), settings.radix),
+            Column::Micros => format_int(conversion.epoch_us(), settings.radix),
+            Column::Nanos => match conversion.epoch_ns() {
+                Some(val) => format_int(val, settings.radix),
+                None => String::new(),
+            },
+            Column::Iso => conversion
+                .instant
+                .with_timezone(&settings.tz)
+                .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            Column::Local => settings
+                .format
+                .format(&conversion.instant.with_timezone(&settings.tz)),
+            Column::Relative => relative(
+                &conversion.instant,
+                &settings.reference.unwrap_or_else(Utc::now),
+                settings.relative.unwrap_or_default(),
+            ),
+            Column::Zone(zone) => zone_cell(&conversion.instant, zone, settings),
+            Column::Zones | Column::Error => String::new(),
+        }
+    }
+}
+
+pub fn default_columns() -> Vec<Column> {
+    vec![
+        Column::Input,
+        Column::Epoch,
+        Column::Iso,
+        Column::Local,
+        Column::Zones,
+        Column::Error,
+    ]
+}
+
+fn expand_columns(columns: &[Column], settings: &Settings) -> Vec<Column> {
+    columns
+        .iter()
+        .flat_map(|column| match column {
+            Column::Zones => settings
+                .zones
+                .iter()
+                .map(|zone| Column::Zone(zone.clone()))
+                .collect(),
+            _ => vec![column.clone()],
+        })
+        .collect()
+}
+
+pub fn escape(cell: &str, mode: OutputMode) -> String {
+    match mode {
+        // RFC 4180: quote fields with separators, quotes or line breaks and double any quotes
+        OutputMode::Csv => {
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.to_string()
+            }
+        }
+        // TSV can't quote, so use the usual backslash escapes
+        OutputMode::Tsv => cell
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r"),
+        _ => cell
+            .replace('\\', "\\\\")
+            .replace('|', "\\|")
+            .replace(['\n', '\r'], " "),
+    }
+}
+
+fn format_row(cells: &[String], mode: OutputMode) -> String {
+    let escaped: Vec<String> = cells.iter().map(|cell| escape(cell, mode)).collect();
+    match mode {
+        OutputMode::Csv => escaped.join(","),
+        OutputMode::Tsv => escaped.join("\t"),
+        _ => format!("| {} |", escaped.join(" | ")),
+    }
+}
+
+pub fn table_rows(args: &[String], settings: &Settings) -> (Vec<String>, usize) {
+    // the rows along with how many of them hold an error instead of a conversion
+    let mut failures = 0;
+    let columns = expand_columns(&settings.columns, settings);
+    let header: Vec<String> = columns.iter().map(|column| column.header()).collect();
+    let mut rows = vec![format_row(&header, settings.output)];
+    if settings.output == OutputMode::Markdown {
+        rows.push(format!("|{}", "---|".repeat(columns.len())));
+    }
+    for arg in args {
+        let cells: Vec<String> = match convert(arg, settings) {
+            Ok(conversion) => columns
+                .iter()
+                .map(|column| column.cell(&conversion, settings))
+                .collect(),
+            Err(e) => {
+                failures += 1;
+                columns
+                    .iter()
+                    .map(|column| match column {
+                        Column::Input => arg.clone(),
+                        Column::Error => e.clone(),
+                        _ => String::new(),
+                    })
+                    .collect()
+            }
+        };
+        rows.push(format_row(&cells, settings.output));
+        if settings.fail_fast && failures > 0 {
+            break;
+        }
+    }
+    (rows, failures)
+}
+
+pub fn print_table(args: &[String], settings: &Settings) -> usize {
+    let (rows, failures) = table_rows(args, settings);
+    for row in rows {
+        println!("{}", row);
+    }
+    failures
+}
+
+#[cfg(test)]
+mod table_output_tests {
+    use super::{escape, table_rows, Column};
+    use crate::settings::{OutputMode, Settings};
+    use crate::timezone::Zone;
+    use chrono::FixedOffset;
+    use chrono_tz::Asia::Kolkata;
+
+    fn settings(output: OutputMode, columns: Vec<Column>) -> Settings {
+        Settings {
+            zones: vec![Zone::Named(Kolkata)],
+            output,
+            columns,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_csv_rows() {
+        let args = vec!["1650627609".to_string(), "bogus".to_string()];
+        let columns = vec![Column::Input, Column::Epoch, Column::Zones, Column::Error];
+        assert_eq!(
+            table_rows(&args, &settings(OutputMode::Csv, columns)),
+            (
+                vec![
+                    "input,epoch,Asia/Kolkata,error".to_string(),
+                    "1650627609,1650627609,04-22-2022 17:10:09 +05:30,".to_string(),
+                    "bogus,,,Invalid Pattern".to_string(),
+                ],
+                1
+            )
+        );
+    }
+
+    #[test]
+    fn test_markdown_rows() {
+        let args = vec!["5-1-93".to_string()];
+        let columns = vec![Column::Input, Column::Kind, Column::Millis, Column::Iso];
+        assert_eq!(
+            table_rows(&args, &settings(OutputMode::Markdown, columns)).0,
+            vec![
+                "| input | kind | ms | iso |",
+                "|---|---|---|---|",
+                "| 5-1-93 | date | 736214400000 | 1993-05-01T00:00:00Z |",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(escape("a,b", OutputMode::Csv), "\"a,b\"");
+        assert_eq!(escape("say \"hi\"", OutputMode::Csv), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape("plain", OutputMode::Csv), "plain");
+        assert_eq!(escape("a\tb", OutputMode::Tsv), "a\\tb");
+        assert_eq!(escape("a|b", OutputMode::Markdown), "a\\|b");
+    }
+
+    #[test]
+    fn test_column_names() {
+        assert_eq!(Column::from_string("ms"), Ok(Column::Millis));
+        assert_eq!(
+            Column::from_string("Asia/Kolkata"),
+            Ok(Column::Zone(Zone::Named(Kolkata)))
+        );
+        assert_eq!(
+            Column::from_string("+05:30"),
+            Ok(Column::Zone(Zone::Fixed(
+                FixedOffset::east_opt(19800).unwrap()
+            )))
+        );
+        assert!(Column::from_string("bogus").is_err());
+    }
+}
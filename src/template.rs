@@ -0,0 +1,241 @@
+// User supplied output lines such as "{input}\t{epoch_ms}\t{zone:Asia/Tokyo}".
+// Parsed once up front so a typo in a placeholder is reported before anything is converted.
+
+use crate::conversion::Conversion;
+use crate::output_format::OutputFormat;
+use crate::radix::format_int;
+use crate::relative::relative;
+use crate::settings::Settings;
+use crate::timezone::Zone;
+use chrono::{SecondsFormat, Utc};
+
+pub const PLACEHOLDERS: &str = "input, kind, output, epoch, epoch_ms, epoch_us, epoch_ns, iso, local, local:<format>, tz, relative, zone:<tz>, zone:<tz>:<format>";
+
+enum Field {
+    Input,
+    Kind,
+    // the right hand side of the usual "input => output" line
+    Output,
+    Epoch,
+    EpochMs,
+    EpochUs,
+    EpochNs,
+    Iso,
+    Local(Option<OutputFormat>),
+    Tz,
+    Relative,
+    Zone(Zone, Option<OutputFormat>),
+}
+
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+fn parse_format(spec: Option<&str>) -> Result<Option<OutputFormat>, String> {
+    match spec {
+        Some(val) => Ok(Some(OutputFormat::parse(val)?)),
+        None => Ok(None),
+    }
+}
+
+fn parse_field(placeholder: &str) -> Result<Field, String> {
+    let (name, spec) = match placeholder.split_once(':') {
+        Some((name, spec)) => (name, Some(spec)),
+        None => (placeholder, None),
+    };
+    let field = match (name, spec) {
+        ("input", None) => Field::Input,
+        ("kind", None) => Field::Kind,
+        ("output", None) => Field::Output,
+        ("epoch", None) => Field::Epoch,
+        ("epoch_ms", None) => Field::EpochMs,
+        ("epoch_us", None) => Field::EpochUs,
+        ("epoch_ns", None) => Field::EpochNs,
+        ("iso", None) => Field::Iso,
+        ("local", spec) => Field::Local(parse_format(spec)?),
+        ("tz", None) => Field::Tz,
+        ("relative", None) => Field::Relative,
+        ("zone", Some(spec)) => {
+            // offsets such as +05:30 contain colons too, so the zone is the longest prefix that parses
+            let split = std::iter::once((spec, None))
+                .chain(
+                    spec.rmatch_indices(':')
+                        .map(|(at, _)| (&spec[..at], Some(&spec[at + 1..]))),
+                )
+                .find_map(|(zone, format)| Zone::parse(zone).ok().map(|zone| (zone, format)));
+            match split {
+                Some((zone, format)) => Field::Zone(zone, parse_format(format)?),
+                None => return Err(format!("Invalid timezone in template: {}", spec)),
+            }
+        }
+        _ => {
+            return Err(format!(
+                "Unknown template placeholder: {{{}}}. Use one of {}.",
+                placeholder, PLACEHOLDERS
+            ))
+        }
+    };
+    Ok(field)
+}
+
+impl Template {
+    pub fn parse(input: &str) -> Result<Template, String> {
+        // {{ and }} are literal braces
+        let mut segments: Vec<Segment> = Vec::new();
+        let mut literal = String::new();
+        let mut chars = input.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(val) => placeholder.push(val),
+                            None => return Err(format!("Unclosed {{ in template: {}", input)),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(parse_field(&placeholder)?));
+                }
+                '}' => return Err(format!("Unmatched }} in template: {}", input)),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Template { segments })
+    }
+
+    pub fn render(&self, conversion: &Conversion, settings: &Settings) -> String {
+        let instant = &conversion.instant;
+        // a format given in the template follows the locale like the main one does
+        let format_with = |format: &Option<OutputFormat>| match (format, settings.locale) {
+            (Some(val), Some(locale)) => val.clone().localized(locale),
+            (Some(val), None) => val.clone(),
+            (None, _) => settings.format.clone(),
+        };
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(val) => val.clone(),
+                Segment::Field(field) => match field {
+                    Field::Input => conversion.input.clone(),
+                    Field::Kind => conversion.kind.name().to_string(),
+                    Field::Output => conversion.output(settings),
+                    Field::Epoch => format_int(conversion.epoch(), settings.radix),
+                    Field::EpochMs => format_int(conversion.epoch_ms(), settings.radix),
+                    Field::EpochUs => format_int(conversion.epoch_us(), settings.radix),
+                    Field::EpochNs => match conversion.epoch_ns() {
+                        Some(val) => format_int(val, settings.radix),
+                        None => "out of range".to_string(),
+                    },
+                    Field::Iso => instant
+                        .with_timezone(&settings.tz)
+                        .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                    Field::Local(format) => {
+                        format_with(format).format(&instant.with_timezone(&settings.tz))
+                    }
+                    Field::Tz => settings.tz.to_string(),
+                    Field::Relative => relative(
+                        instant,
+                        &settings.reference.unwrap_or_else(Utc::now),
+                        settings.relative.unwrap_or_default(),
+                    ),
+                    Field::Zone(zone, format) => {
+                        format_with(format).format(&instant.with_timezone(zone))
+                    }
+                },
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod template_tests {
+    use super::Template;
+    use crate::conversion::convert;
+    use crate::settings::Settings;
+
+    fn render(template: &str, arg: &str) -> String {
+        let settings = Settings::default();
+        let conversion = convert(arg, &settings).unwrap();
+        Template::parse(template)
+            .unwrap()
+            .render(&conversion, &settings)
+    }
+
+    #[test]
+    fn test_fields() {
+        assert_eq!(
+            render("{input}\t{epoch}\t{epoch_ms}\t{iso}\t{tz}", "1650627609123"),
+            "1650627609123\t1650627609\t1650627609123\t2022-04-22T11:40:09.123Z\tUTC"
+        );
+        assert_eq!(
+            render("{input} is a {kind} => {output}", "2022-04-22"),
+            "2022-04-22 is a date => 1650585600"
+        );
+    }
+
+    #[test]
+    fn test_formats_and_zones() {
+        assert_eq!(
+            render("{local:%H:%M} / {zone:Asia/Tokyo}", "1650627609"),
+            "11:40 / 04-22-2022 20:40:09"
+        );
+        assert_eq!(
+            render(
+                "{zone:America/New_York:%H:%M %Z} {local:rfc2822}",
+                "1650627609"
+            ),
+            "07:40 EDT Fri, 22 Apr 2022 11:40:09 +0000"
+        );
+    }
+
+    #[test]
+    fn test_offset_zones() {
+        assert_eq!(
+            render("{zone:+05:30} / {zone:+05:30:%H:%M}", "1650627609"),
+            "04-22-2022 17:10:09 / 17:10"
+        );
+        assert_eq!(
+            render("{zone:EST5EDT,M3.2.0,M11.1.0:%H:%M %Z}", "1650627609"),
+            "07:40 EDT"
+        );
+    }
+
+    #[test]
+    fn test_literal_braces() {
+        assert_eq!(
+            render("{{\"ts\": {epoch}}}", "1650627609"),
+            "{\"ts\": 1650627609}"
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(Template::parse("{bogus}").is_err());
+        assert!(Template::parse("{epoch").is_err());
+        assert!(Template::parse("epoch}").is_err());
+        assert!(Template::parse("{zone:Mars/Olympus}").is_err());
+        assert!(Template::parse("{zone}").is_err());
+        assert!(Template::parse("{zone:+25:00}").is_err());
+        assert!(Template::parse("{local:%Q}").is_err());
+    }
+}
@@ -0,0 +1,587 @@
+// The active timezone: an IANA name, a fixed offset (+05:30, UTC-3) or a POSIX TZ rule
+// (EST5EDT,M3.2.0,M11.1.0). chrono_tz only knows the first, so this wraps all three in
+// one chrono TimeZone and everything downstream can keep calling with_timezone.
+
+use chrono::{
+    Datelike, Days, Duration, FixedOffset, LocalResult, NaiveDate, NaiveDateTime, NaiveTime,
+    Offset, TimeZone, Utc,
+};
+use chrono_tz::{OffsetComponents, Tz, UTC};
+use std::fmt;
+
+// chrono_tz doesn't export its offset type by name
+type TzOffset = <Tz as TimeZone>::Offset;
+
+// When a POSIX rule names a DST zone but not when it applies, use the current US rules
+const DEFAULT_DST_RULES: &str = "M3.2.0,M11.1.0";
+const DEFAULT_TRANSITION_SECS: i64 = 2 * 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RuleDate {
+    // Jn: day 1 to 365, February 29th is never counted
+    Julian(u32),
+    // n: day 0 to 365, counting February 29th
+    ZeroBased(u32),
+    // Mm.w.d: day d (0 is Sunday) of week w (5 is the last) of month m
+    MonthWeekDay(u32, u32, u32),
+}
+
+impl RuleDate {
+    fn in_year(&self, year: i32) -> Option<NaiveDate> {
+        match *self {
+            RuleDate::Julian(day) => {
+                let leap_shift = NaiveDate::from_ymd_opt(year, 2, 29).is_some() && day >= 60;
+                NaiveDate::from_yo_opt(year, day + leap_shift as u32)
+            }
+            // day 365 only exists in leap years, otherwise it's January 1st of the next
+            RuleDate::ZeroBased(day) => {
+                NaiveDate::from_yo_opt(year, 1)?.checked_add_days(Days::new(day as u64))
+            }
+            RuleDate::MonthWeekDay(month, week, weekday) => {
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let first_match = (7 + weekday - first.weekday().num_days_from_sunday()) % 7;
+                let mut day = 1 + first_match + (week - 1) * 7;
+                // week 5 means the last one, which may be the 4th
+                while NaiveDate::from_ymd_opt(year, month, day).is_none() {
+                    day -= 7;
+                }
+                NaiveDate::from_ymd_opt(year, month, day)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Transition {
+    date: RuleDate,
+    // local wall clock seconds after midnight, may be negative or past 24h
+    time: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DstRule {
+    name: String,
+    offset: FixedOffset,
+    start: Transition,
+    end: Transition,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PosixTz {
+    source: String,
+    std_name: String,
+    std_offset: FixedOffset,
+    dst: Option<DstRule>,
+}
+
+struct PosixParser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> PosixParser<'a> {
+    fn name(&mut self) -> Option<String> {
+        // either <+0530> style quoted names or three or more letters
+        if let Some(quoted) = self.rest.strip_prefix('<') {
+            let end = quoted.find('>')?;
+            self.rest = &quoted[end + 1..];
+            return Some(quoted[..end].to_string());
+        }
+        let end = self
+            .rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(self.rest.len());
+        if end < 3 {
+            return None;
+        }
+        let name = self.rest[..end].to_string();
+        self.rest = &self.rest[end..];
+        Some(name)
+    }
+
+    fn seconds(&mut self) -> Option<i64> {
+        // [+-]hh[:mm[:ss]]
+        let (sign, rest) = match self.rest.as_bytes().first() {
+            Some(b'-') => (-1, &self.rest[1..]),
+            Some(b'+') => (1, &self.rest[1..]),
+            _ => (1, self.rest),
+        };
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != ':')
+            .unwrap_or(rest.len());
+        let mut total = 0;
+        for (index, part) in rest[..end].split(':').enumerate() {
+            if index > 2 || part.is_empty() || part.len() > 3 {
+                return None;
+            }
+            let value: i64 = part.parse().ok()?;
+            total += value * [3600, 60, 1][index];
+        }
+        self.rest = &rest[end..];
+        Some(sign * total)
+    }
+
+    fn utc_offset(&mut self) -> Option<FixedOffset> {
+        // POSIX offsets count hours west of Greenwich, the opposite of ISO 8601
+        FixedOffset::west_opt(self.seconds()? as i32)
+    }
+
+    fn transition(&mut self) -> Option<Transition> {
+        let date = if let Some(rest) = self.rest.strip_prefix('M') {
+            let end = rest.find([',', '/']).unwrap_or(rest.len());
+            let parts: Vec<u32> = rest[..end]
+                .split('.')
+                .map(|part| part.parse().ok())
+                .collect::<Option<Vec<u32>>>()?;
+            self.rest = &rest[end..];
+            match parts[..] {
+                [month @ 1..=12, week @ 1..=5, weekday @ 0..=6] => {
+                    RuleDate::MonthWeekDay(month, week, weekday)
+                }
+                _ => return None,
+            }
+        } else {
+            let julian = self.rest.starts_with('J');
+            if julian {
+                self.rest = &self.rest[1..];
+            }
+            let end = self
+                .rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(self.rest.len());
+            let day: u32 = self.rest[..end].parse().ok()?;
+            self.rest = &self.rest[end..];
+            match (julian, day) {
+                (true, 1..=365) => RuleDate::Julian(day),
+                (false, 0..=365) => RuleDate::ZeroBased(day),
+                _ => return None,
+            }
+        };
+        let time = match self.rest.strip_prefix('/') {
+            Some(rest) => {
+                self.rest = rest;
+                self.seconds()?
+            }
+            None => DEFAULT_TRANSITION_SECS,
+        };
+        Some(Transition { date, time })
+    }
+}
+
+impl PosixTz {
+    pub fn parse(input: &str) -> Option<PosixTz> {
+        let mut parser = PosixParser { rest: input };
+        let std_name = parser.name()?;
+        let std_offset = parser.utc_offset()?;
+        if parser.rest.is_empty() {
+            return Some(PosixTz {
+                source: input.to_string(),
+                std_name,
+                std_offset,
+                dst: None,
+            });
+        }
+
+        let dst_name = parser.name()?;
+        let dst_offset = match parser.rest.starts_with(',') || parser.rest.is_empty() {
+            true => FixedOffset::east_opt(std_offset.local_minus_utc() + 3600)?,
+            false => parser.utc_offset()?,
+        };
+        if parser.rest.is_empty() {
+            parser.rest = DEFAULT_DST_RULES;
+        } else {
+            parser.rest = parser.rest.strip_prefix(',')?;
+        }
+        let start = parser.transition()?;
+        parser.rest = parser.rest.strip_prefix(',')?;
+        let end = parser.transition()?;
+        if !parser.rest.is_empty() {
+            return None;
+        }
+        Some(PosixTz {
+            source: input.to_string(),
+            std_name,
+            std_offset,
+            dst: Some(DstRule {
+                name: dst_name,
+                offset: dst_offset,
+                start,
+                end,
+            }),
+        })
+    }
+
+    fn transition_utc(transition: &Transition, year: i32, before: FixedOffset) -> Option<i64> {
+        // transitions are given in the wall clock time in force just before them
+        let midnight = transition.date.in_year(year)?.and_time(NaiveTime::MIN);
+        Some(
+            Utc.from_utc_datetime(&midnight).timestamp() + transition.time
+                - before.local_minus_utc() as i64,
+        )
+    }
+
+    fn is_dst_at(&self, utc: &NaiveDateTime) -> bool {
+        let dst = match &self.dst {
+            Some(val) => val,
+            None => return false,
+        };
+        let timestamp = Utc.from_utc_datetime(utc).timestamp();
+        // at the very edge of chrono's range there's no local year, so stay on standard time
+        let year = match utc
+            .checked_add_signed(Duration::seconds(self.std_offset.local_minus_utc() as i64))
+        {
+            Some(local) => local.year(),
+            None => return false,
+        };
+        let start = Self::transition_utc(&dst.start, year, self.std_offset);
+        let end = Self::transition_utc(&dst.end, year, dst.offset);
+        match (start, end) {
+            // northern hemisphere: DST in the middle of the year
+            (Some(start), Some(end)) if start < end => timestamp >= start && timestamp < end,
+            // southern hemisphere: DST wraps around the new year
+            (Some(start), Some(end)) => timestamp >= start || timestamp < end,
+            _ => false,
+        }
+    }
+
+    fn offset_at(&self, utc: &NaiveDateTime) -> ZoneOffset {
+        ZoneOffset::Posix {
+            rule: Box::new(self.clone()),
+            dst: self.is_dst_at(utc),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Zone {
+    Named(Tz),
+    Fixed(FixedOffset),
+    Posix(Box<PosixTz>),
+}
+
+impl Default for Zone {
+    fn default() -> Self {
+        Zone::Named(UTC)
+    }
+}
+
+fn parse_fixed_offset(input: &str) -> Option<FixedOffset> {
+    // +05:30, -0300, +5, UTC-3, GMT+05:30. Unlike POSIX, the sign means east of UTC.
+    let upper = input.to_ascii_uppercase();
+    let rest = upper
+        .strip_prefix("UTC")
+        .or_else(|| upper.strip_prefix("GMT"))
+        .unwrap_or(&upper);
+    let (sign, digits) = match rest.as_bytes().first() {
+        Some(b'+') => (1, &rest[1..]),
+        Some(b'-') => (-1, &rest[1..]),
+        _ => return None,
+    };
+    // split_at below needs ASCII, and hours and minutes are only ever digits anyway
+    if !digits
+        .bytes()
+        .all(|byte| byte.is_ascii_digit() || byte == b':')
+    {
+        return None;
+    }
+    let (hours, minutes) = match digits.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if digits.len() == 4 => digits.split_at(2),
+        None => (digits, "0"),
+    };
+    if hours.is_empty() || hours.len() > 2 || minutes.is_empty() || minutes.len() > 2 {
+        return None;
+    }
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+impl Zone {
+    pub fn parse(input: &str) -> Result<Zone, String> {
+        if let Ok(tz) = input.parse::<Tz>() {
+            return Ok(Zone::Named(tz));
+        }
+        if let Some(offset) = parse_fixed_offset(input) {
+            return Ok(Zone::Fixed(offset));
+        }
+        match PosixTz::parse(input) {
+            Some(rule) => Ok(Zone::Posix(Box::new(rule))),
+            None => Err(format!("Invalid timezone provided: {}", input)),
+        }
+    }
+}
+
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Zone::Named(tz) => write!(f, "{}", tz),
+            Zone::Fixed(offset) => write!(f, "{}", offset),
+            Zone::Posix(rule) => write!(f, "{}", rule.source),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZoneOffset {
+    Named(TzOffset),
+    Fixed(FixedOffset),
+    Posix { rule: Box<PosixTz>, dst: bool },
+}
+
+impl ZoneOffset {
+    pub fn is_dst(&self) -> bool {
+        match self {
+            ZoneOffset::Named(offset) => !offset.dst_offset().is_zero(),
+            ZoneOffset::Fixed(_) => false,
+            ZoneOffset::Posix { dst, .. } => *dst,
+        }
+    }
+}
+
+impl Offset for ZoneOffset {
+    fn fix(&self) -> FixedOffset {
+        match self {
+            ZoneOffset::Named(offset) => offset.fix(),
+            ZoneOffset::Fixed(offset) => *offset,
+            ZoneOffset::Posix { rule, dst } => match (&rule.dst, dst) {
+                (Some(rule), true) => rule.offset,
+                _ => rule.std_offset,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ZoneOffset {
+    // what %Z prints
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ZoneOffset::Named(offset) => write!(f, "{}", offset),
+            ZoneOffset::Fixed(offset) => write!(f, "{}", offset),
+            ZoneOffset::Posix { rule, dst } => match (&rule.dst, dst) {
+                (Some(rule), true) => write!(f, "{}", rule.name),
+                _ => write!(f, "{}", rule.std_name),
+            },
+        }
+    }
+}
+
+impl TimeZone for Zone {
+    type Offset = ZoneOffset;
+
+    fn from_offset(offset: &ZoneOffset) -> Zone {
+        match offset {
+            ZoneOffset::Named(offset) => Zone::Named(Tz::from_offset(offset)),
+            ZoneOffset::Fixed(offset) => Zone::Fixed(*offset),
+            ZoneOffset::Posix { rule, .. } => Zone::Posix(rule.clone()),
+        }
+    }
+
+    fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<ZoneOffset> {
+        self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+    }
+
+    fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<ZoneOffset> {
+        match self {
+            Zone::Named(tz) => tz.offset_from_local_datetime(local).map(ZoneOffset::Named),
+            Zone::Fixed(offset) => LocalResult::Single(ZoneOffset::Fixed(*offset)),
+            Zone::Posix(rule) => {
+                // Try the local time as standard and as DST time and keep whichever agree with
+                // the rule: none in the spring gap, both in the autumn overlap.
+                let mut candidates: Vec<(NaiveDateTime, ZoneOffset)> = [false, true]
+                    .iter()
+                    .map(|dst| ZoneOffset::Posix {
+                        rule: rule.clone(),
+                        dst: *dst,
+                    })
+                    .filter_map(|offset| {
+                        let shift = Duration::seconds(offset.fix().local_minus_utc() as i64);
+                        match local.checked_sub_signed(shift) {
+                            Some(utc) if rule.offset_at(&utc) == offset => Some((utc, offset)),
+                            Some(_) => None,
+                            // out of chrono's range, where is_dst_at falls back to standard time too
+                            None => match offset {
+                                ZoneOffset::Posix { dst: false, .. } => Some((*local, offset)),
+                                _ => None,
+                            },
+                        }
+                    })
+                    .collect();
+                candidates.sort_by_key(|(utc, _)| *utc);
+                match candidates.len() {
+                    2 => LocalResult::Ambiguous(candidates[0].1.clone(), candidates[1].1.clone()),
+                    1 => LocalResult::Single(candidates.remove(0).1),
+                    _ => LocalResult::None,
+                }
+            }
+        }
+    }
+
+    fn offset_from_utc_date(&self, utc: &NaiveDate) -> ZoneOffset {
+        self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+    }
+
+    fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> ZoneOffset {
+        match self {
+            Zone::Named(tz) => ZoneOffset::Named(tz.offset_from_utc_datetime(utc)),
+            Zone::Fixed(offset) => ZoneOffset::Fixed(*offset),
+            Zone::Posix(rule) => rule.offset_at(utc),
+        }
+    }
+}
+
+#[cfg(test)]
+mod timezone_tests {
+    use super::Zone;
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use chrono_tz::America::New_York;
+
+    fn local(zone: &str, timestamp: i64) -> String {
+        Utc.timestamp_opt(timestamp, 0)
+            .unwrap()
+            .with_timezone(&Zone::parse(zone).unwrap())
+            .format("%F %T %:z %Z")
+            .to_string()
+    }
+
+    const APRIL_TWENTY_SECOND: i64 = 1650627609;
+    const JAN_TEN_TWENTY_TWO: i64 = 1641794400;
+
+    #[test]
+    fn test_named() {
+        assert_eq!(Zone::parse("America/New_York"), Ok(Zone::Named(New_York)));
+        assert_eq!(
+            local("America/New_York", APRIL_TWENTY_SECOND),
+            "2022-04-22 07:40:09 -04:00 EDT"
+        );
+    }
+
+    #[test]
+    fn test_fixed_offsets() {
+        assert_eq!(
+            local("+05:30", APRIL_TWENTY_SECOND),
+            "2022-04-22 17:10:09 +05:30 +05:30"
+        );
+        assert_eq!(
+            local("UTC-3", APRIL_TWENTY_SECOND),
+            "2022-04-22 08:40:09 -03:00 -03:00"
+        );
+        assert_eq!(
+            local("-0930", APRIL_TWENTY_SECOND),
+            "2022-04-22 02:10:09 -09:30 -09:30"
+        );
+        assert_eq!(Zone::parse("+05:30").unwrap().to_string(), "+05:30");
+        assert!(Zone::parse("+25:00").is_err());
+        assert!(Zone::parse("+05:75").is_err());
+        assert!(Zone::parse("+a€").is_err());
+        assert!(Zone::parse("UTC+€5").is_err());
+    }
+
+    #[test]
+    fn test_posix_matches_iana() {
+        let rule = "EST5EDT,M3.2.0,M11.1.0";
+        assert_eq!(
+            local(rule, APRIL_TWENTY_SECOND),
+            "2022-04-22 07:40:09 -04:00 EDT"
+        );
+        assert_eq!(
+            local(rule, JAN_TEN_TWENTY_TWO),
+            "2022-01-10 01:00:00 -05:00 EST"
+        );
+        // every hour through 2022 agrees with the tz database
+        let zone = Zone::parse(rule).unwrap();
+        for hour in 0..(365 * 24) {
+            let instant = Utc.timestamp_opt(1640995200 + hour * 3600, 0).unwrap();
+            assert_eq!(
+                instant.with_timezone(&zone).offset().to_string(),
+                instant.with_timezone(&New_York).offset().to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_posix_southern_and_quoted() {
+        // Australia/Sydney: DST from the first Sunday in October to the first Sunday in April
+        let rule = "AEST-10AEDT,M10.1.0,M4.1.0/3";
+        assert_eq!(
+            local(rule, JAN_TEN_TWENTY_TWO),
+            "2022-01-10 17:00:00 +11:00 AEDT"
+        );
+        assert_eq!(
+            local(rule, APRIL_TWENTY_SECOND),
+            "2022-04-22 21:40:09 +10:00 AEST"
+        );
+        assert_eq!(
+            local("<+0530>-5:30", APRIL_TWENTY_SECOND),
+            "2022-04-22 17:10:09 +05:30 +0530"
+        );
+    }
+
+    #[test]
+    fn test_posix_zero_based_days() {
+        // 2022 isn't a leap year, so day 365 is January 1st 2023 and DST lasts the whole year
+        assert_eq!(
+            local("EST5EDT,0,365", APRIL_TWENTY_SECOND),
+            "2022-04-22 07:40:09 -04:00 EDT"
+        );
+        assert_eq!(
+            local("EST5EDT,0,365", JAN_TEN_TWENTY_TWO),
+            "2022-01-10 02:00:00 -04:00 EDT"
+        );
+    }
+
+    #[test]
+    fn test_posix_local_times() {
+        let zone = Zone::parse("CST6CDT,M3.2.0,M11.1.0").unwrap();
+        let datetime = |h, m| {
+            NaiveDate::from_ymd_opt(2022, 3, 13)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+        // spring forward gap
+        assert!(zone
+            .from_local_datetime(&datetime(2, 30))
+            .single()
+            .is_none());
+        assert_eq!(
+            zone.from_local_datetime(&datetime(3, 30))
+                .unwrap()
+                .timestamp(),
+            1647160200
+        );
+        // fall back overlap
+        let overlap = NaiveDate::from_ymd_opt(2022, 11, 6)
+            .unwrap()
+            .and_hms_opt(1, 30, 0)
+            .unwrap();
+        assert!(zone.from_local_datetime(&overlap).earliest().is_some());
+        assert!(zone.from_local_datetime(&overlap).single().is_none());
+    }
+
+    #[test]
+    fn test_posix_range_bounds() {
+        // the ends of chrono's range can't be shifted into local time, so they stay on standard time
+        let zone = Zone::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        let first = NaiveDate::MIN.and_hms_opt(0, 0, 0).unwrap();
+        let last = NaiveDate::MAX.and_hms_opt(23, 59, 59).unwrap();
+        assert!(zone.from_utc_datetime(&first).to_string().ends_with("EST"));
+        // and local times that would leave the range once shifted to UTC are none, not a panic
+        assert!(zone.from_local_datetime(&last).single().is_none());
+        let southern = Zone::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert!(southern
+            .from_utc_datetime(&last)
+            .to_string()
+            .ends_with("AEST"));
+        assert!(southern.from_local_datetime(&first).single().is_none());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Zone::parse("Mars/Olympus").is_err());
+        assert!(Zone::parse("EST5EDT,M13.1.0,M11.1.0").is_err());
+        assert!(Zone::parse("AB5").is_err());
+    }
+}
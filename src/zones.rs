@@ -0,0 +1,136 @@
+// Multi-zone output: one row per argument, one column per configured zone.
+
+use crate::conversion::convert;
+use crate::radix::format_int;
+use crate::settings::Settings;
+use crate::timezone::Zone;
+use chrono::{DateTime, Offset, Utc};
+
+pub fn zone_cell(instant: &DateTime<Utc>, zone: &Zone, settings: &Settings) -> String {
+    // The formatted time plus its UTC offset, marked when daylight saving is in effect.
+    let local = instant.with_timezone(zone);
+    let dst_marker = match local.offset().is_dst() {
+        true => " DST",
+        false => "",
+    };
+    format!(
+        "{} {}{}",
+        settings.format.format(&local),
+        local.offset().fix(),
+        dst_marker
+    )
+}
+
+pub fn print_zone_table(args: &[String], settings: &Settings) -> usize {
+    // returns how many arguments couldn't be converted, those are reported on stderr instead of a row
+    let mut failures = 0;
+    let mut rows: Vec<Vec<String>> = vec![["Input", "Epoch"]
+        .iter()
+        .map(|val| val.to_string())
+        .chain(settings.zones.iter().map(|zone| zone.to_string()))
+        .collect()];
+    for arg in args {
+        match convert(arg, settings) {
+            Ok(val) => {
+                for note in &val.notes {
+                    eprintln!("{}", note);
+                }
+                let mut row = vec![val.input.clone(), format_int(val.epoch(), settings.radix)];
+                row.extend(
+                    settings
+                        .zones
+                        .iter()
+                        .map(|zone| zone_cell(&val.instant, zone, settings)),
+                );
+                rows.push(row);
+            }
+            Err(e) => {
+                eprintln!("Unable to parse value: {} ({})", arg, e);
+                failures += 1;
+                if settings.fail_fast {
+                    break;
+                }
+            }
+        }
+    }
+
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    for row in rows {
+        let padded: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    }
+    failures
+}
+
+#[cfg(test)]
+mod zones_tests {
+    use super::zone_cell;
+    use crate::settings::Settings;
+    use crate::timezone::Zone;
+    use chrono::{FixedOffset, TimeZone, Utc};
+    use chrono_tz::{America::New_York, Asia::Kolkata, Europe::London, UTC};
+
+    fn settings() -> Settings {
+        Settings::default()
+    }
+
+    #[test]
+    fn test_summer_cells() {
+        const APRIL_TWENTY_SECOND: i64 = 1650627609;
+        let instant = Utc.timestamp_opt(APRIL_TWENTY_SECOND, 0).unwrap();
+        assert_eq!(
+            zone_cell(&instant, &Zone::Named(New_York), &settings()),
+            "04-22-2022 07:40:09 -04:00 DST"
+        );
+        assert_eq!(
+            zone_cell(&instant, &Zone::Named(London), &settings()),
+            "04-22-2022 12:40:09 +01:00 DST"
+        );
+        assert_eq!(
+            zone_cell(&instant, &Zone::Named(Kolkata), &settings()),
+            "04-22-2022 17:10:09 +05:30"
+        );
+    }
+
+    #[test]
+    fn test_winter_cells() {
+        const JAN_TEN_TWENTY_TWO: i64 = 1641794400;
+        let instant = Utc.timestamp_opt(JAN_TEN_TWENTY_TWO, 0).unwrap();
+        assert_eq!(
+            zone_cell(&instant, &Zone::Named(New_York), &settings()),
+            "01-10-2022 01:00:00 -05:00"
+        );
+        assert_eq!(
+            zone_cell(&instant, &Zone::Named(UTC), &settings()),
+            "01-10-2022 06:00:00 +00:00"
+        );
+    }
+
+    #[test]
+    fn test_offset_and_posix_cells() {
+        const APRIL_TWENTY_SECOND: i64 = 1650627609;
+        let instant = Utc.timestamp_opt(APRIL_TWENTY_SECOND, 0).unwrap();
+        let offset = Zone::Fixed(FixedOffset::east_opt(19800).unwrap());
+        assert_eq!(
+            zone_cell(&instant, &offset, &settings()),
+            "04-22-2022 17:10:09 +05:30"
+        );
+        let posix = Zone::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        assert_eq!(
+            zone_cell(&instant, &posix, &settings()),
+            "04-22-2022 07:40:09 -04:00 DST"
+        );
+    }
+}